@@ -2,6 +2,8 @@ pub mod list_item;
 pub use list_item::*;
 pub mod message;
 pub use message::*;
+pub mod message_text;
+pub use message_text::*;
 pub mod avatar;
 pub use avatar::*;
 pub mod text_input;
@@ -3,8 +3,12 @@ use pelican_ui::drawable::{Component, Drawable};
 use pelican_ui::layout::{SizeRequest, Area, Layout};
 use pelican_ui::events::OnEvent;
 use pelican_ui::resources;
+use pelican_ui::hardware::ImageOrientation;
+use std::sync::mpsc::Sender;
 
+use crate::service::RoomMetadata;
 use pelican_ui_std::{Avatar, AvatarContent, AvatarIconStyle, Row};
+use base64::{engine::general_purpose::STANDARD, Engine};
 
 pub struct AvatarMessages;
 impl AvatarMessages {
@@ -16,6 +20,26 @@ impl AvatarMessages {
     pub fn room(ctx: &mut Context, content: AvatarContent) -> Avatar {
         Avatar::new(ctx, content, None, false, 128.0, None)
     }
+
+    pub fn group_with_edit(ctx: &mut Context, content: AvatarContent, sender: Sender<(Vec<u8>, ImageOrientation)>) -> Avatar {
+        Avatar::new(ctx, content,
+            Some(("edit", AvatarIconStyle::Secondary)), false, 128.0,
+            Some(Box::new(move |ctx: &mut Context| {
+                ctx.hardware.open_photo_picker(sender.clone());
+            })),
+        )
+    }
+}
+
+pub struct EncodedImageMessages;
+impl EncodedImageMessages {
+    /// Like `EncodedImage::decode`, but gives `None` for data that isn't a base64 image instead of panicking,
+    /// as images written by other members can't be trusted.
+    pub fn decode(ctx: &mut Context, data: &str) -> Option<resources::Image> {
+        let bytes = STANDARD.decode(data).ok()?;
+        let image = image::load_from_memory(&bytes).ok()?;
+        Some(ctx.assets.add_image(image.into()))
+    }
 }
 
 pub struct AvatarContentMessages;
@@ -28,6 +52,14 @@ impl AvatarContentMessages {
         }
     }
 
+    /// The group's avatar, or the group icon when it has none or it can't be decoded.
+    pub fn group(ctx: &mut Context, metadata: &RoomMetadata) -> AvatarContent {
+        match metadata.avatar().and_then(|avatar| EncodedImageMessages::decode(ctx, avatar)) {
+            Some(image) => AvatarContent::Image(image),
+            None => AvatarContent::Icon("group", AvatarIconStyle::Secondary),
+        }
+    }
+
     pub fn default() -> AvatarContent {
         AvatarContent::Icon("door", AvatarIconStyle::Secondary)
     }
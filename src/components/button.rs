@@ -1,5 +1,5 @@
 use pelican_ui::Context;
-use pelican_ui_std::AppPage;
+use pelican_ui_std::{AppPage, Button, ButtonSize, ButtonWidth, ButtonStyle, ButtonState, Offset};
 use profiles::pages::Account;
 
 type MessagesButton = (&'static str, Box<dyn FnMut(&mut Context) -> Box<dyn AppPage>>);
@@ -32,4 +32,15 @@ impl IconButtonMessages {
 
         ("messages", closure)
     }
-}
\ No newline at end of file
+}
+
+pub struct ButtonMessages;
+impl ButtonMessages {
+    pub const REACTIONS: [&'static str; 6] = ["👍", "❤️", "😂", "😮", "😢", "🙏"];
+
+    pub fn reaction(ctx: &mut Context, emoji: &str, count: usize, selected: bool, on_click: impl FnMut(&mut Context) + 'static) -> Button {
+        let label = format!("{} {}", emoji, count);
+        let state = if selected {ButtonState::Selected} else {ButtonState::Default};
+        Button::new(ctx, None, None, Some(&label), None, ButtonSize::Medium, ButtonWidth::Hug, ButtonStyle::Secondary, state, Offset::Center, on_click, None)
+    }
+}
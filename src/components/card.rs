@@ -10,10 +10,11 @@ use pelican_ui_std::{
     OutlinedRectangle, 
     Rectangle, Avatar, 
     AvatarContent, ButtonState,
-    ExpandableText, NavigateEvent
+    ExpandableText,
 };
 
-use crate::service::PublicRoom;
+use crate::service::{PublicRoom, Access};
+use crate::events::JoinRoomEvent;
 
 #[derive(Component)]
 pub struct Card(Stack, OutlinedRectangle, CardContent, #[skip] ButtonState, #[skip] Box<dyn FnMut(&mut Context)>);
@@ -93,11 +94,17 @@ impl CardContent {
 pub struct Cards(Column, Vec<Card>);
 impl OnEvent for Cards {}
 impl Cards {
-    pub fn new(ctx: &mut Context, rooms: Vec<PublicRoom>, index: usize) -> Self {
-        let rooms = rooms.into_iter().map(|mut room| {
-            let desc = format!("{} members", room.members().len());
-            Card::new(ctx, room.avatar().clone(), &room.title().clone(), &desc.clone(), room.subtitle(), move |ctx: &mut Context| {
-                ctx.trigger_event(NavigateEvent(index))
+    pub fn new(ctx: &mut Context, rooms: Vec<PublicRoom>) -> Self {
+        let rooms = rooms.into_iter().map(|room| {
+            let mut members = match room.members().len() {
+                1 => "1 member".to_string(),
+                count => format!("{} members", count),
+            };
+            if room.metadata().access() == Access::InviteOnly {members.push_str(" · Invite only");}
+            let avatar = room.avatar(ctx);
+            let id = room.id();
+            Card::new(ctx, avatar, &room.title(), &members, &room.description(), move |ctx: &mut Context| {
+                ctx.trigger_event(JoinRoomEvent(id))
             })
         }).collect::<Vec<_>>();
         Cards(Column::new(24.0, Offset::Center, Size::Fit, Padding(24.0, 16.0, 24.0, 16.0)), rooms)
@@ -3,12 +3,14 @@ use pelican_ui::drawable::Align;
 use profiles::components::AvatarContentProfiles;
 use pelican_ui::air::OrangeName;
 use profiles::plugin::ProfilePlugin;
-use crate::components::AvatarRow;
+use crate::components::{AvatarRow, EncodedImageMessages};
+use crate::service::RoomMetadata;
 
 use pelican_ui_std::{
     IconButton, Header, 
     Text, TextStyle, NavigateEvent,
     HeaderContent, HeaderIcon,
+    AvatarContent,
 };
 
 
@@ -21,17 +23,20 @@ impl HeaderMessages {
         let right = IconButton::navigation(ctx, "info", move |ctx: &mut Context| ctx.trigger_event(NavigateEvent(1)));
         Header::new(HeaderIcon::new(Some(left)), HeaderContentMessages::new(ctx, profiles), HeaderIcon::new(Some(right)))
     }
+
+    pub fn group(ctx: &mut Context, profiles: Vec<OrangeName>, metadata: &RoomMetadata) -> Header {
+        let left = IconButton::navigation(ctx, "left", move |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
+        let right = IconButton::navigation(ctx, "info", move |ctx: &mut Context| ctx.trigger_event(NavigateEvent(1)));
+        Header::new(HeaderIcon::new(Some(left)), HeaderContentMessages::group(ctx, profiles, metadata), HeaderIcon::new(Some(right)))
+    }
 }
 
 pub struct HeaderContentMessages;
 
 impl HeaderContentMessages {
     #[allow(clippy::new_ret_no_self)]
-    pub fn new(ctx: &mut Context, mut profiles: Vec<OrangeName>) -> HeaderContent {
-        let me = ProfilePlugin::me(ctx).0;
-        profiles.retain(|p| *p != me);
-        if profiles.is_empty() { profiles.push(me); }
-        let profiles: Vec<_> = profiles.into_iter().map(|p| (ProfilePlugin::username(ctx, &p).to_string(), AvatarContentProfiles::from_orange_name(ctx, &p))).collect();
+    pub fn new(ctx: &mut Context, profiles: Vec<OrangeName>) -> HeaderContent {
+        let profiles = Self::profiles(ctx, profiles);
         let title = if profiles.len() == 1 { profiles[0].0.clone() } else { "Group Message".to_string() };
         let avatars = profiles.into_iter().map(|(_, a)| a).collect();
 
@@ -40,4 +45,26 @@ impl HeaderContentMessages {
             Text::new(ctx, &title, TextStyle::Heading, ctx.theme.fonts.size.h5, Align::Left),
         )
     }
-}
\ No newline at end of file
+
+    /// Uses the group's name and avatar when they are set, falling back to its members.
+    pub fn group(ctx: &mut Context, profiles: Vec<OrangeName>, metadata: &RoomMetadata) -> HeaderContent {
+        let profiles = Self::profiles(ctx, profiles);
+        let title = metadata.name().cloned().unwrap_or("Group Message".to_string());
+        let avatars = match metadata.avatar().and_then(|avatar| EncodedImageMessages::decode(ctx, avatar)) {
+            Some(image) => vec![AvatarContent::Image(image)],
+            None => profiles.into_iter().map(|(_, a)| a).collect(),
+        };
+
+        HeaderContent::new(
+            Some(Box::new(AvatarRow::new(ctx, avatars))),
+            Text::new(ctx, &title, TextStyle::Heading, ctx.theme.fonts.size.h5, Align::Left),
+        )
+    }
+
+    fn profiles(ctx: &mut Context, mut profiles: Vec<OrangeName>) -> Vec<(String, AvatarContent)> {
+        let me = ProfilePlugin::me(ctx).0;
+        profiles.retain(|p| *p != me);
+        if profiles.is_empty() { profiles.push(me); }
+        profiles.into_iter().map(|p| (ProfilePlugin::username(ctx, &p).to_string(), AvatarContentProfiles::from_orange_name(ctx, &p))).collect()
+    }
+}
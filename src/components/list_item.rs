@@ -17,13 +17,15 @@ use pelican_ui_std::{
     Size, Wrap,
     ListItemGroup,
     Column, ListItem,
-    AvatarContent,
-    AvatarIconStyle,
     NavigateEvent,
+    Timestamp,
 };
 
-use crate::events::{RemoveContactEvent, AddContactEvent, SetRoomEvent};
-use crate::service::{Room, Message};
+use chrono::Local;
+
+use crate::components::AvatarContentMessages;
+use crate::events::{RemoveContactEvent, AddContactEvent, SetRoomEvent, JoinRoomEvent};
+use crate::service::{Room, Rooms, Message, Member, RoomMetadata, Role, PublicRoom, Access, Report};
 
 pub struct ListItemGroupMessages;
 
@@ -32,15 +34,16 @@ impl ListItemGroupMessages {
         rooms.sort_by_key(|room| room.2.last().map(|msg| *msg.timestamp()));
 
         let items = rooms.into_iter().rev().map(|room| {
+            let me = ProfilePlugin::me(ctx).0;
+            let read = !Rooms::is_unread(&room, &me);
             match room.1.len() > 2 {
-                true => ListItemMessages::group_message(ctx, room.1.clone(), room.2.clone(), move |ctx: &mut Context| {
+                true => ListItemMessages::group_message(ctx, room.1.iter().map(|m| m.name().clone()).collect(), &room.5, read, move |ctx: &mut Context| {
                     ctx.trigger_event(SetRoomEvent(room.0));
                     ctx.trigger_event(NavigateEvent(1));
                 }),
                 false => {
-                    let me = ProfilePlugin::me(ctx).0;
-                    let user = room.1.into_iter().filter(|orange_name| *orange_name != me).collect::<Vec<_>>().last().unwrap_or(&me).clone();
-                    ListItemMessages::direct_message(ctx, user, room.2.clone(), move |ctx: &mut Context| {
+                    let user = room.1.iter().map(Member::name).rfind(|orange_name| **orange_name != me).unwrap_or(&me).clone();
+                    ListItemMessages::direct_message(ctx, user, room.2.clone(), read, move |ctx: &mut Context| {
                         ctx.trigger_event(SetRoomEvent(room.0));
                         ctx.trigger_event(NavigateEvent(2));
                     })
@@ -62,6 +65,21 @@ impl ListItemMessages {
         ListItem::new(ctx, true, &name, None, Some(orange), None, None, None, None, Some(data), None, true, on_click)
     }
 
+    /// A group member, described by who added them and when they joined, with admins marked as such.
+    pub fn member(ctx: &mut Context, member: &Member, on_click: impl FnMut(&mut Context) + 'static) -> ListItem {
+        let name = ProfilePlugin::username(ctx, member.name());
+        let data = AvatarContentProfiles::from_orange_name(ctx, member.name());
+        let joined = Timestamp::new(member.joined().with_timezone(&Local)).direct().unwrap_or_default();
+        let description = match member.inviter() {
+            Some(inviter) => format!("Added by {} · {}", ProfilePlugin::username(ctx, inviter), joined),
+            None => format!("Joined · {}", joined),
+        };
+        let orange = member.name().to_string();
+        let orange = orange.strip_prefix("orange_name:").unwrap_or(orange.as_str());
+        let role = (member.role() == Role::Admin).then_some("Admin");
+        ListItem::new(ctx, true, &name, None, Some(orange), Some(&description), role, None, None, Some(data), None, true, on_click)
+    }
+
     pub fn recipient(ctx: &mut Context, orange_name: &OrangeName) -> ListItem {
         let name = ProfilePlugin::username(ctx, orange_name);
         let data = AvatarContentProfiles::from_orange_name(ctx, orange_name);
@@ -74,29 +92,60 @@ impl ListItemMessages {
         )
     }
 
-    pub fn direct_message(ctx: &mut Context, other: OrangeName, mut messages: Vec<Message>, on_click: impl FnMut(&mut Context) + 'static) -> ListItem {
+    pub fn direct_message(ctx: &mut Context, other: OrangeName, mut messages: Vec<Message>, read: bool, on_click: impl FnMut(&mut Context) + 'static) -> ListItem {
         let me = ProfilePlugin::me(ctx).0;
         let other_name = ProfilePlugin::username(ctx, &other);
         let data = AvatarContentProfiles::from_orange_name(ctx, &other);
-        messages.retain(|m| *m.message() != "__system__joined");
-        let (recent, read) = &messages.last().map(|m| {
+        messages.retain(|m| !m.is_system());
+        let recent = &messages.last().map(|m| {
             let prefix = if *m.author() == me {"You".to_string()} else {other_name.clone()};
-            (format!("{}: {}", prefix, m.message().clone()), *m.is_read())
-        }).unwrap_or(("No messages yet.".to_string(), true));
+            let text = match m.is_deleted() {
+                true => "Message deleted".to_string(),
+                false if m.message().is_empty() && let Some(attachment) = m.attachment() => attachment.name().to_string(),
+                false => m.message().clone(),
+            };
+            format!("{}: {}", prefix, text)
+        }).unwrap_or("No messages yet.".to_string());
         let color = ctx.theme.colors.brand.primary;
         ListItem::new(ctx, true, &other_name, (!read).then_some(("notification", color)), Some(recent), None, None, None, None, Some(data), None, true, on_click)
     }
 
-    pub fn group_message(ctx: &mut Context, names: Vec<OrangeName>, messages: Vec<Message>, on_click: impl FnMut(&mut Context) + 'static) -> ListItem {
-        let read = messages.last().map(|m| *m.is_read()).unwrap_or(true);
+    pub fn group_message(ctx: &mut Context, names: Vec<OrangeName>, metadata: &RoomMetadata, read: bool, on_click: impl FnMut(&mut Context) + 'static) -> ListItem {
         let me = ProfilePlugin::me(ctx).0;
         let names = names.iter().filter(|orange| **orange != me).map(|orange_name| {
             ProfilePlugin::username(ctx, orange_name).trim().to_string()
         }).collect::<Vec<String>>();
         let names = names.join(", ");
-        let avatar = AvatarContent::Icon("group", AvatarIconStyle::Secondary);
+        let title = metadata.name().cloned().unwrap_or("Group Message".to_string());
+        let avatar = AvatarContentMessages::group(ctx, metadata);
         let color = ctx.theme.colors.brand.primary;
-        ListItem::new(ctx, true, "Group Message", (!read).then_some(("notification", color)), None, Some(&names), None, None, None, Some(avatar), None, true, on_click)
+        ListItem::new(ctx, true, &title, (!read).then_some(("notification", color)), metadata.topic().map(|t| t.as_str()), Some(&names), None, None, None, Some(avatar), None, true, on_click)
+    }
+
+    /// A public room, marked when I'm already in it.
+    pub fn public_room(ctx: &mut Context, room: &PublicRoom, on_click: impl FnMut(&mut Context) + 'static) -> ListItem {
+        let me = ProfilePlugin::me(ctx).0;
+        let avatar = room.avatar(ctx);
+        let members = match room.members().len() {
+            1 => "1 member".to_string(),
+            count => format!("{} members", count),
+        };
+        let status = match (room.is_member(&me), room.metadata().access()) {
+            (true, _) => Some("Joined"),
+            (false, Access::InviteOnly) => Some("Invite only"),
+            (false, Access::Open) => None,
+        };
+        ListItem::new(ctx, true, &room.title(), None, Some(&members), Some(&room.description()), status, None, None, Some(avatar), None, true, on_click)
+    }
+
+    /// A reported message, under the name of who wrote it and with the reason it was reported.
+    pub fn report(ctx: &mut Context, report: &Report, message: &Message, on_click: impl FnMut(&mut Context) + 'static) -> ListItem {
+        let name = ProfilePlugin::username(ctx, message.author());
+        let data = AvatarContentProfiles::from_orange_name(ctx, message.author());
+        let reporter = format!("Reported by {}", ProfilePlugin::username(ctx, report.reporter()));
+        let text = if message.is_deleted() {"Message deleted"} else {message.message().as_str()};
+        let reason = Some(report.reason().as_str()).filter(|r| !r.is_empty()).unwrap_or(text);
+        ListItem::new(ctx, true, &name, None, Some(&reporter), Some(reason), None, None, None, Some(data), None, true, on_click)
     }
 
     // pub fn room(ctx: &mut Context, data: AvatarContent, name: &str, members: &str, description: &str, on_click: impl FnMut(&mut Context) + 'static) -> ListItem {
@@ -198,6 +247,44 @@ impl OnEvent for QuickDeselect {
     }
 }
 
+/// Public rooms filtered live by the searchbar: title matches first, then description, then tags.
+#[derive(Debug, Component)]
+pub struct RoomSearchResults(Column, ListItemGroup, #[skip] Vec<PublicRoom>);
+
+impl RoomSearchResults {
+    pub fn new(ctx: &mut Context, rooms: Vec<PublicRoom>) -> Self {
+        let items = rooms.iter().map(|room| {
+            let id = room.id();
+            ListItemMessages::public_room(ctx, room, move |ctx: &mut Context| {
+                ctx.trigger_event(JoinRoomEvent(id));
+            })
+        }).collect();
+        RoomSearchResults(Column::new(24.0, Offset::Start, Size::Fit, Padding::default()), ListItemGroup::new(items), rooms)
+    }
+}
+
+impl OnEvent for RoomSearchResults {
+    fn on_event(&mut self, _ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(SearchEvent(query)) = event.downcast_ref::<SearchEvent>() {
+            let query = query.to_lowercase();
+
+            let mut items_and_rooms: Vec<_> = self.1.inner().drain(..).zip(self.2.drain(..)).map(|(item, room)| {
+                let priority = if query.is_empty() || room.title().to_lowercase().contains(&query) {0}
+                    else if room.description().to_lowercase().contains(&query) {1}
+                    else if room.metadata().tags().iter().any(|tag| tag.to_lowercase().contains(&query)) {2}
+                    else {3};
+                (priority, item, room)
+            }).collect();
+
+            items_and_rooms.sort_by_key(|(priority, _, _)| *priority);
+            let flags = items_and_rooms.iter().map(|(priority, _, _)| *priority == 3).collect::<Vec<_>>();
+            (*self.1.inner(), self.2) = items_and_rooms.into_iter().map(|(_, item, room)| (item, room)).unzip();
+            flags.into_iter().enumerate().for_each(|(i, flag)| self.1.hide(flag, i));
+        }
+        true
+    }
+}
+
 #[derive(Debug, Component)]
 struct QuickDeselectContent(Wrap, Vec<QuickDeselectButton>);
 impl OnEvent for QuickDeselectContent {}
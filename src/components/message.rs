@@ -1,5 +1,5 @@
-use pelican_ui::events::OnEvent;
-use pelican_ui::drawable::{Drawable, Component, Align};
+use pelican_ui::events::{OnEvent, Event, MouseState, MouseEvent, TickEvent};
+use pelican_ui::drawable::{Drawable, Component, Align, Color, Image};
 use pelican_ui::layout::{Area, SizeRequest, Layout};
 use pelican_ui::{Context, Component};
 
@@ -7,10 +7,14 @@ use profiles::plugin::ProfilePlugin;
 use profiles::components::AvatarContentProfiles;
 use pelican_ui::air::OrangeName;
 
-use chrono::{Duration, Local};
+use chrono::{DateTime, Duration, Local, Utc};
+use std::collections::BTreeMap;
 
-use crate::service::Message;
-use crate::components::AvatarMessages;
+use uuid::Uuid;
+use crate::service::{Message, MessageKind, ReadCursors, Delivery, Role, Attachment, Attachments};
+use crate::components::{AvatarMessages, ButtonMessages, MessageText, EncodedImageMessages};
+use crate::events::{OpenMessageEvent, OpenThreadEvent, ReactEvent, ScrollToMessageEvent, RetryMessageEvent, OpenImageEvent, SaveFileEvent};
+use crate::plugin::MessagesPlugin;
 
 use pelican_ui_std::{
     Padding,
@@ -24,6 +28,10 @@ use pelican_ui_std::{
     Timestamp,
     Row,
     Avatar,
+    Wrap,
+    Button,
+    AdjustScrollEvent,
+    AspectRatioImage,
 };
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -43,7 +51,9 @@ impl TextMessage {
     pub fn new(
         ctx: &mut Context,
         mut style: MessageType,
-        messages: Vec<String>,
+        messages: Vec<Message>,
+        room: &[Message],
+        receipts: &BTreeMap<Uuid, String>,
         author: OrangeName,
         timestamp: Timestamp
     ) -> Self {
@@ -60,11 +70,13 @@ impl TextMessage {
         TextMessage (
             Row::new(8.0, offset, Size::Fit, Padding::default()),
             avatar.then(|| AvatarMessages::new(ctx, avatar_content)),
-            MessageContent::new(ctx, style, messages, &username, timestamp)
+            MessageContent::new(ctx, style, messages, room, receipts, &username, timestamp)
         )
     }
 
     fn content(&mut self) -> &mut MessageContent {&mut self.2}
+
+    fn position(&self, ctx: &mut Context, key: &Uuid) -> Option<f32> {self.2.position(ctx, key)}
 }
 
 #[derive(Debug, Component)]
@@ -75,7 +87,9 @@ impl MessageContent {
     fn new(
         ctx: &mut Context,
         style: MessageType,
-        messages: Vec<String>,
+        messages: Vec<Message>,
+        room: &[Message],
+        receipts: &BTreeMap<Uuid, String>,
         name: &str,
         time: Timestamp,
     ) -> Self {
@@ -97,11 +111,16 @@ impl MessageContent {
 
         MessageContent(
             Column::new(8.0, offset, Size::custom(|widths: Vec<(f32, f32)>| (widths[1].0, f32::MAX)), Padding::default()),
-            top, MessageBubbles::new(ctx, messages, style), bottom
+            top, MessageBubbles::new(ctx, messages, room, receipts, style), bottom
         )
     }
 
     fn bubbles(&mut self) -> &mut MessageBubbles {&mut self.2}
+
+    fn position(&self, ctx: &mut Context, key: &Uuid) -> Option<f32> {
+        let top = self.1.as_ref().map(|data| Drawable::request_size(data, ctx).max_height() + 8.0).unwrap_or_default();
+        self.2.position(ctx, key).map(|y| top + y)
+    }
 }
 
 #[derive(Debug, Component)]
@@ -139,42 +158,348 @@ impl OnEvent for MessageBubbles {}
 impl MessageBubbles {
     fn new(
         ctx: &mut Context,
-        messages: Vec<String>,
-        // message: &str,
+        messages: Vec<Message>,
+        room: &[Message],
+        receipts: &BTreeMap<Uuid, String>,
         style: MessageType,
     ) -> Self {
-        let messages = messages.iter().map(|m| MessageBubble::new(ctx, m.as_str(), style)).collect();
+        let messages = messages.iter().map(|m| MessageBubble::new(ctx, m, room, receipts.get(&m.id()), style)).collect();
         let offset = if style == MessageType::You { Offset::End } else { Offset::Start };
         MessageBubbles(Column::new(8.0, offset, Size::Fit, Padding::default()), messages)
     }
 
     fn bubbles(&mut self) -> &mut Vec<MessageBubble> {&mut self.1}
+
+    fn position(&self, ctx: &mut Context, key: &Uuid) -> Option<f32> {
+        let mut y = 0.0;
+        for bubble in &self.1 {
+            if bubble.10.as_ref() == Some(key) { return Some(y); }
+            y += Drawable::request_size(bubble, ctx).max_height() + 8.0;
+        }
+        None
+    }
 }
 
 #[derive(Debug, Component)]
-struct MessageBubble(Stack, RoundedRectangle, Text);
-impl OnEvent for MessageBubble {}
+struct MessageBubble(Column, Option<Quote>, Option<ImageThumbnail>, Option<FileCard>, Option<Bubble>, Option<Text>, Option<ReactionChips>, Option<ThreadLink>, Option<Text>, Option<DeliveryStatus>, #[skip] Option<Uuid>);
+
+impl OnEvent for MessageBubble {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let (Some(MouseEvent{state: MouseState::ReleasedLong, position: Some(_)}), Some(key)) = (event.downcast_ref::<MouseEvent>(), &self.10) {
+            ctx.trigger_event(OpenMessageEvent(*key))
+        }
+        true
+    }
+}
 
 impl MessageBubble {
+    fn new(
+        ctx: &mut Context,
+        message: &Message,
+        room: &[Message],
+        receipt: Option<&String>,
+        style: MessageType,
+    ) -> Self {
+        let text_size = ctx.theme.fonts.size.xs;
+        let offset = if style == MessageType::You { Offset::End } else { Offset::Start };
+        let edited = message.is_edited().then(|| Text::new(ctx, "edited", TextStyle::Secondary, text_size, Align::Left));
+        let key = message.id();
+        let (image, file) = match message.attachment() {
+            Some(Attachment::Image(thumbnail)) => (Some(ImageThumbnail::new(ctx, thumbnail, key)), None),
+            Some(Attachment::File(name, size)) => (None, Some(FileCard::new(ctx, name, *size, key))),
+            None => (None, None),
+        };
+        let bubble = match message.is_deleted() {
+            true => Some(Bubble::deleted(ctx)),
+            false if message.attachment().is_some() && message.message().is_empty() => None,
+            false => Some(Bubble::new(ctx, message.message(), style))
+        };
+        let reactions = (!message.reactions().is_empty()).then(|| ReactionChips::new(ctx, message));
+        let quote = message.reply_to().and_then(|parent| room.iter().find(|m| m.id() == *parent)).map(|parent| {
+            Quote::new(ctx, parent, Some((key, parent.id())))
+        });
+        let replies = room.iter().filter(|m| m.thread() == Some(&key)).count();
+        let thread = (replies > 0).then(|| ThreadLink::new(ctx, key, replies));
+        let receipt = receipt.map(|r| Text::new(ctx, r, TextStyle::Secondary, text_size, Align::Left));
+        let delivery = message.delivery().map(|d| DeliveryStatus::new(ctx, d, key));
+        MessageBubble(
+            Column::new(4.0, offset, Size::Fit, Padding::default()),
+            quote,
+            image,
+            file,
+            bubble,
+            edited,
+            reactions,
+            thread,
+            receipt,
+            delivery,
+            (!message.is_deleted()).then_some(key)
+        )
+    }
+}
+
+/// A photo in a message, as wide as a bubble. Tapping it opens the full image.
+/// A thumbnail that isn't a valid image shows a placeholder tile instead.
+#[derive(Debug, Component)]
+struct ImageThumbnail(Stack, Option<Image>, Option<ImagePlaceholder>, #[skip] Uuid);
+
+impl OnEvent for ImageThumbnail {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(MouseEvent{state: MouseState::Released, position: Some(_)}) = event.downcast_ref::<MouseEvent>() {
+            ctx.trigger_event(OpenImageEvent(self.3))
+        }
+        true
+    }
+}
+
+impl ImageThumbnail {
+    const WIDTH: f32 = 240.0;
+
+    fn new(ctx: &mut Context, thumbnail: &str, key: Uuid) -> Self {
+        match EncodedImageMessages::decode(ctx, thumbnail) {
+            Some(image) => ImageThumbnail(Stack::default(), Some(AspectRatioImage::new(image, (Self::WIDTH, Self::WIDTH))), None, key),
+            None => ImageThumbnail(Stack::default(), None, Some(ImagePlaceholder::new(ctx, Self::WIDTH)), key),
+        }
+    }
+}
+
+#[derive(Debug, Component)]
+struct ImagePlaceholder(Stack, RoundedRectangle, Text);
+impl OnEvent for ImagePlaceholder {}
+
+impl ImagePlaceholder {
+    fn new(ctx: &mut Context, width: f32) -> Self {
+        let text_size = ctx.theme.fonts.size.sm;
+        let background = RoundedRectangle::new(0.0, 16.0, ctx.theme.colors.background.secondary);
+        let text = Text::new(ctx, "This photo can't be shown", TextStyle::Secondary, text_size, Align::Center);
+        let layout = Stack(Offset::Center, Offset::Center, Size::Static(width), Size::Static(width*0.75), Padding::default());
+        ImagePlaceholder(layout, background, text)
+    }
+}
+
+/// A file in a message with its name, size and, where `MessagesPlugin::FILES`, a button to save it.
+/// Saving asks for the file first when it hasn't been loaded, and finishes once it arrives.
+#[derive(Debug, Component)]
+pub struct FileCard(Stack, RoundedRectangle, FileCardContent, #[skip] Uuid, #[skip] String, #[skip] bool);
+
+impl OnEvent for FileCard {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(SaveFileEvent(key)) = event.downcast_ref::<SaveFileEvent>() && *key == self.3 {
+            match ctx.state().get_or_default::<Attachments>().get(self.3).is_some() {
+                true => self.save(ctx),
+                false => {
+                    MessagesPlugin::load_attachment(ctx, self.3);
+                    self.2.2.text().spans[0].text = "Downloading…".to_string();
+                    self.5 = true;
+                }
+            }
+        } else if let Some(TickEvent) = event.downcast_ref::<TickEvent>() && self.5
+            && ctx.state().get_or_default::<Attachments>().get(self.3).is_some() {
+            self.save(ctx);
+            self.5 = false;
+        }
+        true
+    }
+}
+
+impl FileCard {
+    const WIDTH: f32 = 240.0;
+
+    fn new(ctx: &mut Context, name: &str, size: u64, key: Uuid) -> Self {
+        let (hp, vp) = (12.0, 12.0);
+        let background = RoundedRectangle::new(0.0, 16.0, ctx.theme.colors.background.secondary);
+        let layout = Stack(
+            Offset::Center, Offset::Center,
+            Size::Static(Self::WIDTH+(hp*2.)),
+            Size::custom(move |heights: Vec<(f32, f32)>| (heights[1].0+(vp*2.), heights[1].1+(vp*2.))),
+            Padding::default()
+        );
+        FileCard(layout, background, FileCardContent::new(ctx, name, size, key, Self::WIDTH), key, name.to_string(), false)
+    }
+
+    fn save(&mut self, ctx: &mut Context) {
+        let status = match MessagesPlugin::save_file(ctx, self.3, &self.4) {
+            Ok(path) => format!("Saved to {}", path.display()),
+            Err(error) => error,
+        };
+        self.2.2.text().spans[0].text = status;
+    }
+
+    /// A byte count the way people read it, such as "1.4 MB".
+    pub fn size(bytes: u64) -> String {
+        match bytes {
+            b if b < 1024 => format!("{} B", b),
+            b if b < 1024 * 1024 => format!("{:.1} KB", b as f64 / 1024.0),
+            b if b < 1024 * 1024 * 1024 => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+            b => format!("{:.1} GB", b as f64 / (1024.0 * 1024.0 * 1024.0)),
+        }
+    }
+}
+
+#[derive(Debug, Component)]
+struct FileCardContent(Column, Text, Text, Option<Button>);
+impl OnEvent for FileCardContent {}
+
+impl FileCardContent {
+    fn new(ctx: &mut Context, name: &str, size: u64, key: Uuid, max_w: f32) -> Self {
+        let text_size = ctx.theme.fonts.size;
+        let mut name = Text::new(ctx, name, TextStyle::Heading, text_size.md, Align::Left);
+        name.text().width = Some(max_w);
+        let mut status = Text::new(ctx, &FileCard::size(size), TextStyle::Secondary, text_size.sm, Align::Left);
+        status.text().width = Some(max_w);
+        let save = MessagesPlugin::FILES.then(|| Button::secondary(ctx, None, "Save", None, move |ctx: &mut Context| ctx.trigger_event(SaveFileEvent(key)), None));
+        FileCardContent(Column::new(8.0, Offset::Start, Size::Fit, Padding::default()), name, status, save)
+    }
+}
+
+#[derive(Debug, Component)]
+pub struct Quote(Stack, RoundedRectangle, QuoteContent, #[skip] Option<(Uuid, Uuid)>);
+
+impl OnEvent for Quote {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let (Some(MouseEvent{state: MouseState::Released, position: Some(_)}), Some((from, to))) = (event.downcast_ref::<MouseEvent>(), &self.3) {
+            ctx.trigger_event(ScrollToMessageEvent(*from, *to))
+        }
+        true
+    }
+}
+
+impl Quote {
+    const SNIPPET: usize = 64;
+
+    pub fn new(ctx: &mut Context, parent: &Message, jump: Option<(Uuid, Uuid)>) -> Self {
+        let (hp, vp) = (8.0, 8.0);
+        let max_w = 300.0-(hp*2.0);
+        let snippet = match parent.is_deleted() {
+            true => "Message deleted".to_string(),
+            false if parent.message().is_empty() && let Some(attachment) = parent.attachment() => attachment.name().to_string(),
+            false if parent.message().chars().count() > Self::SNIPPET => format!("{}…", parent.message().chars().take(Self::SNIPPET).collect::<String>()),
+            false => parent.message().clone(),
+        };
+        let username = ProfilePlugin::username(ctx, parent.author());
+        let background = RoundedRectangle::new(0.0, 8.0, ctx.theme.colors.background.secondary);
+        let layout = Stack(
+            Offset::Center, Offset::Center,
+            Size::custom(move |widths: Vec<(f32, f32)>| {
+                let size = (widths[1].1+(hp*2.)).min(max_w+(hp*2.));
+                (size, size)
+            }),
+            Size::custom(move |heights: Vec<(f32, f32)>| (heights[1].0+vp, heights[1].1+vp)),
+            Padding::default()
+        );
+        Quote(layout, background, QuoteContent::new(ctx, &username, &snippet, max_w), jump)
+    }
+}
+
+#[derive(Debug, Component)]
+struct QuoteContent(Column, Text, Text);
+impl OnEvent for QuoteContent {}
+
+impl QuoteContent {
+    fn new(ctx: &mut Context, name: &str, snippet: &str, max_w: f32) -> Self {
+        let text_size = ctx.theme.fonts.size;
+        let name = Text::new(ctx, name, TextStyle::Heading, text_size.xs, Align::Left);
+        let mut snippet = Text::new(ctx, snippet, TextStyle::Secondary, text_size.sm, Align::Left);
+        snippet.text().width = Some(max_w);
+        QuoteContent(Column::new(2.0, Offset::Start, Size::Fit, Padding::default()), name, snippet)
+    }
+}
+
+#[derive(Debug, Component)]
+struct ThreadLink(Stack, Text, #[skip] Uuid);
+
+impl OnEvent for ThreadLink {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(MouseEvent{state: MouseState::Released, position: Some(_)}) = event.downcast_ref::<MouseEvent>() {
+            ctx.trigger_event(OpenThreadEvent(self.2))
+        }
+        true
+    }
+}
+
+impl ThreadLink {
+    fn new(ctx: &mut Context, key: Uuid, replies: usize) -> Self {
+        let text_size = ctx.theme.fonts.size.sm;
+        let label = if replies == 1 {"1 reply".to_string()} else {format!("{} replies", replies)};
+        let color = ctx.theme.colors.brand.primary;
+        ThreadLink(Stack::default(), Text::new(ctx, &label, TextStyle::Label(color), text_size, Align::Left), key)
+    }
+}
+
+#[derive(Debug, Component)]
+struct DeliveryStatus(Stack, Text, #[skip] Option<Uuid>);
+
+impl OnEvent for DeliveryStatus {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let (Some(MouseEvent{state: MouseState::Released, position: Some(_)}), Some(key)) = (event.downcast_ref::<MouseEvent>(), &self.2) {
+            ctx.trigger_event(RetryMessageEvent(*key))
+        }
+        true
+    }
+}
+
+impl DeliveryStatus {
+    fn new(ctx: &mut Context, delivery: Delivery, key: Uuid) -> Self {
+        let text_size = ctx.theme.fonts.size.xs;
+        let (label, style) = match delivery {
+            Delivery::Pending => ("Sending…", TextStyle::Secondary),
+            Delivery::Sent => ("Sent", TextStyle::Secondary),
+            Delivery::Failed => ("Not sent · Tap to retry", TextStyle::Error),
+        };
+        let retry = (delivery == Delivery::Failed).then_some(key);
+        DeliveryStatus(Stack::default(), Text::new(ctx, label, style, text_size, Align::Left), retry)
+    }
+}
+
+#[derive(Debug, Component)]
+struct ReactionChips(Wrap, Vec<Button>);
+impl OnEvent for ReactionChips {}
+
+impl ReactionChips {
+    fn new(ctx: &mut Context, message: &Message) -> Self {
+        let me = ProfilePlugin::me(ctx).0;
+        let chips = message.reactions().iter().map(|(emoji, names)| {
+            let (key, emoji) = (message.id(), emoji.clone());
+            ButtonMessages::reaction(ctx, &emoji.clone(), names.len(), names.contains(&me), move |ctx: &mut Context| {
+                ctx.trigger_event(ReactEvent(key, emoji.clone()))
+            })
+        }).collect();
+        ReactionChips(Wrap::new(4.0, 4.0), chips)
+    }
+}
+
+#[derive(Debug, Component)]
+struct Bubble(Stack, RoundedRectangle, MessageText);
+impl OnEvent for Bubble {}
+
+impl Bubble {
     fn new(
         ctx: &mut Context,
         message: &str,
         style: MessageType,
     ) -> Self {
-        let text_size = ctx.theme.fonts.size.md;
         let colors = &ctx.theme.colors;
-        let (bg_color, text_style) = match style {
-            MessageType::You => (colors.brand.primary, TextStyle::White),
-            MessageType::Rooms => (colors.background.primary, TextStyle::White),
-            MessageType::Group => (colors.background.secondary, TextStyle::Primary),
-            MessageType::Contact => (colors.background.secondary, TextStyle::Primary),
+        let (bg_color, code_color, text_style, link_style) = match style {
+            MessageType::You => (colors.brand.primary, colors.shades.darken, TextStyle::White, TextStyle::Label(colors.shades.white)),
+            MessageType::Rooms => (colors.background.primary, colors.background.secondary, TextStyle::White, TextStyle::Label(colors.brand.primary)),
+            MessageType::Group => (colors.background.secondary, colors.background.primary, TextStyle::Primary, TextStyle::Label(colors.brand.primary)),
+            MessageType::Contact => (colors.background.secondary, colors.background.primary, TextStyle::Primary, TextStyle::Label(colors.brand.primary)),
         };
+        Self::build(ctx, message, bg_color, code_color, text_style, link_style)
+    }
 
+    fn deleted(ctx: &mut Context) -> Self {
+        let colors = &ctx.theme.colors;
+        let (bg_color, code_color) = (colors.background.secondary, colors.background.primary);
+        Self::build(ctx, "Message deleted", bg_color, code_color, TextStyle::Secondary, TextStyle::Secondary)
+    }
+
+    fn build(ctx: &mut Context, message: &str, bg_color: Color, code_color: Color, text_style: TextStyle, link_style: TextStyle) -> Self {
+        let text_size = ctx.theme.fonts.size.md;
         let (hp, vp) = (12.0, 12.0);
         let max_w = 300.0-(hp*2.0);
         let background = RoundedRectangle::new(0.0, 16.0, bg_color);
-        let mut content = Text::new(ctx, message, text_style, text_size, Align::Left);
-        content.text().width = Some(max_w);
+        let content = MessageText::new(ctx, message, text_style, link_style, code_color, text_size, max_w);
         let layout = Stack(
             Offset::Center, Offset::Center, 
             Size::custom(move |widths: Vec<(f32, f32)>| {
@@ -185,22 +510,87 @@ impl MessageBubble {
             Padding::default()
         );
 
-        MessageBubble(layout, background, content)
+        Bubble(layout, background, content)
     }
 }
 
+/// A centered line in the timeline describing a change to the room, such as a member joining.
+#[derive(Debug, Component)]
+struct SystemNotice(Stack, Text);
+impl OnEvent for SystemNotice {}
+
+impl SystemNotice {
+    fn new(ctx: &mut Context, message: &Message) -> Self {
+        let me = ProfilePlugin::me(ctx).0;
+        let mut name = |n: &OrangeName| if *n == me {"You".to_string()} else {ProfilePlugin::username(ctx, n)};
+        let author = name(message.author());
+        let notice = match message.kind() {
+            MessageKind::Joined(Some(by)) => format!("{} added {}", name(by), author),
+            MessageKind::Joined(None) => format!("{} joined", author),
+            MessageKind::Left => format!("{} left", author),
+            MessageKind::Removed(member) => format!("{} removed {}", author, name(member)),
+            MessageKind::Renamed(title) => format!("{} renamed the group to \"{}\"", author, title),
+            MessageKind::RoleChanged(member, Role::Admin) => format!("{} made {} an admin", author, name(member)),
+            MessageKind::RoleChanged(member, Role::Member) => format!("{} removed {} as admin", author, name(member)),
+            MessageKind::Text => message.message().clone(),
+        };
+        let text_size = ctx.theme.fonts.size.xs;
+        SystemNotice(Stack::center(), Text::new(ctx, &notice, TextStyle::Secondary, text_size, Align::Center))
+    }
+}
+
+/// One entry in a `TextMessageGroup`: a run of messages from one author, or a system notice.
+#[derive(Debug, Component)]
+struct TimelineItem(Stack, Option<TextMessage>, Option<SystemNotice>);
+impl OnEvent for TimelineItem {}
+
+impl TimelineItem {
+    fn message(message: TextMessage) -> Self {TimelineItem(Stack::center(), Some(message), None)}
+    fn notice(notice: SystemNotice) -> Self {TimelineItem(Stack::center(), None, Some(notice))}
+}
+
 #[derive(Debug, Component)]
-pub struct TextMessageGroup(Column, Vec<TextMessage>);
-impl OnEvent for TextMessageGroup {}
+pub struct TextMessageGroup(Column, Vec<TimelineItem>, #[skip] Vec<Message>, #[skip] ReadCursors);
+
+impl OnEvent for TextMessageGroup {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(ScrollToMessageEvent(from, to)) = event.downcast_ref::<ScrollToMessageEvent>()
+            && let (Some(from), Some(to)) = (self.position(ctx, from), self.position(ctx, to)) {
+            ctx.trigger_event(AdjustScrollEvent::Vertical(to - from));
+        }
+        true
+    }
+}
 
 impl TextMessageGroup {
-    pub fn new(ctx: &mut Context, messages: &Vec<Message>, style: MessageType) -> Self {
+    /// A room's main timeline. Replies in a thread are left out and reached through their parent's thread link.
+    pub fn new(ctx: &mut Context, messages: &[Message], cursors: &ReadCursors, style: MessageType) -> Self {
+        Self::build(ctx, messages, cursors, style, false)
+    }
+
+    /// A thread: its parent followed by every reply.
+    pub fn thread(ctx: &mut Context, messages: &[Message], style: MessageType) -> Self {
+        Self::build(ctx, messages, &ReadCursors::new(), style, true)
+    }
+
+    fn build(ctx: &mut Context, messages: &[Message], cursors: &ReadCursors, style: MessageType, replies: bool) -> Self {
+        let shown = messages.iter().filter(|m| replies || m.thread().is_none()).collect::<Vec<_>>();
+        let receipts = Self::receipts(ctx, &shown, cursors, style);
         let mut result = Vec::new();
         let mut section = Vec::new();
         let mut last_author = None;
-        let mut last_time = None;
+        let mut last_time: Option<&DateTime<Utc>> = None;
+
+        for msg in shown {
+            if msg.is_system() {
+                if let (Some(author), Some(time)) = (last_author.take(), last_time.take()) {
+                    let section = std::mem::take(&mut section);
+                    result.push(TimelineItem::message(TextMessage::new(ctx, style, section, messages, &receipts, author, Timestamp::new(time.with_timezone(&Local)))));
+                }
+                result.push(TimelineItem::notice(SystemNotice::new(ctx, msg)));
+                continue;
+            }
 
-        for msg in messages {
             let time = msg.timestamp();
             let author = msg.author().clone();
 
@@ -208,26 +598,58 @@ impl TextMessageGroup {
             let close_time = last_time.map(|t| (*time - t) <= Duration::minutes(1)).unwrap_or(false);
 
             if same_author && close_time {
-                section.push(msg.message().clone());
+                section.push(msg.clone());
                 last_time = Some(time);
             } else {
                 if let (Some(author), Some(time)) = (last_author, last_time) {
-                    result.push(TextMessage::new(ctx, style, section, author, Timestamp::new(time.with_timezone(&Local))));
+                    result.push(TimelineItem::message(TextMessage::new(ctx, style, section, messages, &receipts, author, Timestamp::new(time.with_timezone(&Local)))));
                 }
-                section = vec![msg.message().clone()];
+                section = vec![msg.clone()];
                 last_author = Some(author);
                 last_time = Some(time);
             }
         }
 
         if let (Some(author), Some(time)) = (last_author, last_time) {
-            result.push(TextMessage::new(ctx, style, section, author, Timestamp::new(time.with_timezone(&Local))));
+            result.push(TimelineItem::message(TextMessage::new(ctx, style, section, messages, &receipts, author, Timestamp::new(time.with_timezone(&Local)))));
         }
 
-        TextMessageGroup(Column::center(24.0), result)
+        TextMessageGroup(Column::center(24.0), result, messages.to_vec(), cursors.clone())
+    }
+
+    /// Places each member's read cursor under the newest message it covers.
+    /// Direct messages only mark my own messages as seen, group messages name every reader.
+    fn receipts(ctx: &mut Context, messages: &[&Message], cursors: &ReadCursors, style: MessageType) -> BTreeMap<Uuid, String> {
+        let me = ProfilePlugin::me(ctx).0;
+        let mut readers: BTreeMap<Uuid, Vec<OrangeName>> = BTreeMap::new();
+        cursors.iter().filter(|(name, _)| **name != me).for_each(|(name, read)| {
+            if let Some(m) = messages.iter().rev().find(|m| !m.is_system() && m.timestamp() <= read && m.author() != name) {
+                readers.entry(m.id()).or_default().push(name.clone());
+            }
+        });
+        readers.into_iter().filter_map(|(key, names)| match style {
+            MessageType::Contact => messages.iter().any(|m| m.id() == key && *m.author() == me).then(|| (key, "Seen".to_string())),
+            MessageType::Group => {
+                let names = names.iter().map(|n| ProfilePlugin::username(ctx, n)).collect::<Vec<_>>().join(", ");
+                Some((key, format!("Seen by {}", names)))
+            },
+            _ => None
+        }).collect()
     }
 
     pub fn count(&mut self) -> usize {
-        self.1.iter_mut().map(|msg| msg.content().bubbles().bubbles().len()).sum()
+        self.1.iter_mut().filter_map(|item| item.1.as_mut()).map(|msg| msg.content().bubbles().bubbles().len()).sum()
+    }
+
+    pub fn messages(&self) -> &Vec<Message> {&self.2}
+    pub fn cursors(&self) -> &ReadCursors {&self.3}
+
+    fn position(&self, ctx: &mut Context, key: &Uuid) -> Option<f32> {
+        let mut y = 0.0;
+        for item in &self.1 {
+            if let Some(offset) = item.1.as_ref().and_then(|message| message.position(ctx, key)) { return Some(y + offset); }
+            y += Drawable::request_size(item, ctx).max_height() + 24.0;
+        }
+        None
     }
 }
@@ -0,0 +1,413 @@
+use pelican_ui::events::{OnEvent, Event, MouseState, MouseEvent};
+use pelican_ui::drawable::{Drawable, Component, Align, Color};
+use pelican_ui::layout::{Area, SizeRequest, Layout};
+use pelican_ui::{Context, Component, resources};
+use pelican_ui::air::OrangeName;
+
+use profiles::plugin::ProfilePlugin;
+use std::str::FromStr;
+
+use crate::events::OpenLinkEvent;
+
+use pelican_ui_std::{
+    Padding,
+    Size,
+    Offset,
+    Stack,
+    Text,
+    TextStyle,
+    RoundedRectangle,
+    Column,
+    Row,
+};
+
+/// Something in a message that can be tapped. The app decides what opening it means.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Link {
+    Url(String),
+    OrangeName(OrangeName),
+    Email(String),
+}
+
+impl Link {
+    /// The link a word in a message stands for, if any.
+    pub fn detect(word: &str) -> Option<Link> {
+        if let Ok(name) = OrangeName::from_str(word) { return Some(Link::OrangeName(name)); }
+        let lower = word.to_lowercase();
+        if (lower.starts_with("https://") || lower.starts_with("http://")) && !word.ends_with("//") {
+            return Some(Link::Url(word.to_string()));
+        }
+        if lower.starts_with("www.") && word.len() > 4 {
+            return Some(Link::Url(format!("https://{}", word)));
+        }
+        let (local, domain) = word.split_once('@')?;
+        let valid = !local.is_empty() && !domain.contains('@')
+            && domain.split('.').count() > 1 && domain.split('.').all(|part| !part.is_empty());
+        valid.then(|| Link::Email(word.to_string()))
+    }
+}
+
+/// The inline styles a run of message text can have.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Emphasis {
+    Plain,
+    Bold,
+    Italic,
+    Code,
+}
+
+impl Emphasis {
+    fn marker(c: char) -> Option<Emphasis> {
+        match c {
+            '*' => Some(Emphasis::Bold),
+            '_' => Some(Emphasis::Italic),
+            '`' => Some(Emphasis::Code),
+            _ => None,
+        }
+    }
+
+    /// Splits a line into runs of `*bold*`, `_italic_` and `` `code` `` between plain text.
+    /// Markers only count when they hug the text they wrap, so `2 * 3` and snake_case stay as typed.
+    /// Links are left whole, and markers inside a run are kept as typed rather than nested.
+    fn runs(line: &str) -> Vec<(String, Emphasis)> {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut runs = Vec::new();
+        let mut plain = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            if i == 0 || chars[i-1].is_whitespace() {
+                let end = (i..chars.len()).find(|j| chars[*j].is_whitespace()).unwrap_or(chars.len());
+                let token = chars[i..end].iter().collect::<String>();
+                if Self::is_link(&token) {
+                    plain.push_str(&token);
+                    i = end;
+                    continue;
+                }
+            }
+            if let Some(emphasis) = Self::marker(chars[i]) && let Some(end) = Self::close(&chars, i, emphasis) {
+                if !plain.is_empty() { runs.push((std::mem::take(&mut plain), Emphasis::Plain)); }
+                runs.push((chars[i+1..end].iter().collect(), emphasis));
+                i = end+1;
+            } else {
+                plain.push(chars[i]);
+                i += 1;
+            }
+        }
+        if !plain.is_empty() { runs.push((plain, Emphasis::Plain)); }
+        runs
+    }
+
+    /// Whether a token is a link, unless a marker opens before it.
+    fn is_link(token: &str) -> bool {
+        MessageText::split_link(token).is_some_and(|(_, core, ..)| !core.starts_with(|c| Self::marker(c).is_some()))
+    }
+
+    /// Where the marker at `start` is closed, if it opens anything.
+    fn close(chars: &[char], start: usize, emphasis: Emphasis) -> Option<usize> {
+        let marker = chars[start];
+        let hugs = |c: Option<&char>| c.is_some_and(|c| !c.is_whitespace() && *c != marker);
+        let word = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric());
+        if !hugs(chars.get(start+1)) { return None; }
+        if emphasis == Emphasis::Code { return (start+2..chars.len()).find(|j| chars[*j] == marker); }
+        if start > 0 && word(chars.get(start-1)) { return None; }
+        (start+2..chars.len()).find(|j| chars[*j] == marker && hugs(chars.get(j-1)) && !word(chars.get(j+1)))
+    }
+}
+
+/// The colors and size a bubble draws its text with.
+#[derive(Clone, Copy)]
+struct Styles {
+    text: TextStyle,
+    link: TextStyle,
+    code: Color,
+    size: f32,
+}
+
+/// The text of a message, laid out a word at a time so links and emphasis can be styled and tapped.
+/// `Text` starts a new line for every span, so each word is its own `Text` packed into rows here.
+/// Supports `*bold*`, `_italic_`, `` `code` ``, fenced code blocks and `-` or `*` bullets.
+/// A fence that is never closed is shown as typed.
+#[derive(Debug, Component)]
+pub struct MessageText(Column, Vec<MessageBlock>);
+impl OnEvent for MessageText {}
+
+impl MessageText {
+    /// Leading and trailing characters that belong to the sentence around a link rather than the link.
+    const OPENING: [char; 4] = ['(', '<', '"', '\''];
+    const CLOSING: [char; 10] = ['.', ',', ';', ':', '!', '?', ')', '>', '"', '\''];
+    const FENCE: &'static str = "```";
+
+    pub fn new(ctx: &mut Context, text: &str, style: TextStyle, link_style: TextStyle, code: Color, size: f32, max_w: f32) -> Self {
+        let styles = Styles{text: style, link: link_style, code, size};
+        let mut blocks = Vec::new();
+        let mut lines = text.split('\n');
+        while let Some(line) = lines.next() {
+            if line.trim_start().starts_with(Self::FENCE)
+                && let Some(end) = lines.clone().position(|l| l.trim_start().starts_with(Self::FENCE)) {
+                let code = lines.by_ref().take(end).collect::<Vec<_>>().join("\n");
+                lines.next();
+                blocks.push(MessageBlock::code(CodeBlock::new(ctx, &code, styles, max_w)));
+                continue;
+            }
+            let item = line.trim_start().strip_prefix("- ").or_else(|| line.trim_start().strip_prefix("* "));
+            let rows = match item {
+                Some(item) => Self::rows(ctx, item, Some("•  "), styles, max_w),
+                None => Self::rows(ctx, line, None, styles, max_w),
+            };
+            blocks.extend(rows.into_iter().map(MessageBlock::line));
+        }
+        MessageText(Column::new(0.0, Offset::Start, Size::Fit, Padding::default()), blocks)
+    }
+
+    /// Wraps a line into rows no wider than `max_w`. Rows after a bullet are indented to line up with its text.
+    fn rows(ctx: &mut Context, line: &str, bullet: Option<&str>, styles: Styles, max_w: f32) -> Vec<MessageLine> {
+        let bullet = bullet.map(|b| MessageWord::new(ctx, b, styles, Emphasis::Plain, None));
+        let indent = bullet.as_ref().map(|b| Drawable::request_size(b, ctx).min_width()).unwrap_or_default();
+        let mut rows = vec![(bullet.into_iter().collect::<Vec<_>>(), indent)];
+        for (run, emphasis) in Emphasis::runs(line) {
+            for token in run.split_inclusive(char::is_whitespace) {
+                let mut words = match emphasis {
+                    Emphasis::Plain => Self::words(ctx, token, styles),
+                    _ => vec![MessageWord::new(ctx, token, styles, emphasis, None)],
+                };
+                let width = words.iter().map(|w| Drawable::request_size(w, ctx).min_width()).sum::<f32>();
+                if width > max_w-indent {
+                    words.iter_mut().for_each(|w| w.2.text().width = Some(max_w-indent));
+                }
+                let used = rows.last().unwrap().1;
+                if used + width > max_w && used > indent {
+                    rows.push((Vec::new(), indent));
+                }
+                let (row, used) = rows.last_mut().unwrap();
+                row.extend(words);
+                *used += width;
+            }
+        }
+        rows.into_iter().enumerate().map(|(i, (mut row, _))| {
+            if row.is_empty() { row.push(MessageWord::new(ctx, "", styles, Emphasis::Plain, None)); }
+            let padding = if i > 0 { Padding(indent, 0.0, 0.0, 0.0) } else { Padding::default() };
+            MessageLine(Row::new(0.0, Offset::End, Size::Fit, padding), row)
+        }).collect()
+    }
+
+    /// The link in a token with the text before and after it, once surrounding punctuation is pulled off.
+    fn split_link(token: &str) -> Option<(&str, &str, Link, &str)> {
+        let trimmed = token.trim_end();
+        let core = trimmed.trim_start_matches(Self::OPENING).trim_end_matches(Self::CLOSING);
+        let link = Link::detect(core)?;
+        let start = trimmed.len() - trimmed.trim_start_matches(Self::OPENING).len();
+        Some((&token[..start], core, link, &token[start+core.len()..]))
+    }
+
+    /// Splits a token into the words that draw it, pulling surrounding punctuation off any link inside.
+    fn words(ctx: &mut Context, token: &str, styles: Styles) -> Vec<MessageWord> {
+        let Some((before, core, link, after)) = Self::split_link(token) else {
+            return vec![MessageWord::new(ctx, token, styles, Emphasis::Plain, None)];
+        };
+        let label = match &link {
+            Link::OrangeName(name) => format!("@{}", ProfilePlugin::username(ctx, name)),
+            _ => core.to_string(),
+        };
+        let mut words = Vec::new();
+        if !before.is_empty() { words.push(MessageWord::new(ctx, before, styles, Emphasis::Plain, None)); }
+        words.push(MessageWord::new(ctx, &label, styles, Emphasis::Plain, Some(link)));
+        if !after.is_empty() { words.push(MessageWord::new(ctx, after, styles, Emphasis::Plain, None)); }
+        words
+    }
+}
+
+/// A row of wrapped text or a code block.
+#[derive(Debug, Component)]
+struct MessageBlock(Stack, Option<MessageLine>, Option<CodeBlock>);
+impl OnEvent for MessageBlock {}
+
+impl MessageBlock {
+    fn line(line: MessageLine) -> Self {MessageBlock(Stack::default(), Some(line), None)}
+    fn code(code: CodeBlock) -> Self {
+        MessageBlock(Stack(Offset::Start, Offset::Start, Size::Fit, Size::Fit, Padding(0.0, 4.0, 0.0, 4.0)), None, Some(code))
+    }
+}
+
+#[derive(Debug, Component)]
+struct MessageLine(Row, Vec<MessageWord>);
+impl OnEvent for MessageLine {}
+
+#[derive(Debug, Component)]
+struct MessageWord(Stack, RoundedRectangle, Text, #[skip] Option<Link>);
+
+impl OnEvent for MessageWord {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let (Some(MouseEvent{state: MouseState::Released, position: Some(_)}), Some(link)) = (event.downcast_ref::<MouseEvent>(), &self.3) {
+            ctx.trigger_event(OpenLinkEvent(link.clone()))
+        }
+        true
+    }
+}
+
+impl MessageWord {
+    /// The theme's fonts have no italic face, so italic keeps the text font in a softer shade of its color.
+    /// Code is set in the `Monospace` font on a background.
+    fn new(ctx: &mut Context, word: &str, styles: Styles, emphasis: Emphasis, link: Option<Link>) -> Self {
+        let style = if link.is_some() { styles.link } else { styles.text };
+        let mut text = Text::new(ctx, word, style, styles.size, Align::Left);
+        let font = match emphasis {
+            Emphasis::Bold => Some(ctx.theme.fonts.fonts.label.clone()),
+            Emphasis::Code => Some(Monospace::font(ctx)),
+            Emphasis::Plain | Emphasis::Italic => None,
+        };
+        let span = &mut text.text().spans[0];
+        if let Some(font) = font {span.font = font;}
+        if emphasis == Emphasis::Italic {
+            span.color = Color(span.color.0, span.color.1, span.color.2, (span.color.3 as f32 * 0.7) as u8);
+        }
+        let color = if emphasis == Emphasis::Code { styles.code } else { ctx.theme.colors.shades.transparent };
+        let layout = Stack(
+            Offset::Start, Offset::Start,
+            Size::custom(|widths: Vec<(f32, f32)>| (widths[1].0, widths[1].0)),
+            Size::custom(|heights: Vec<(f32, f32)>| (heights[1].0, heights[1].0)),
+            Padding::default()
+        );
+        MessageWord(layout, RoundedRectangle::new(0.0, 4.0, color), text, link)
+    }
+}
+
+/// The monospace face code is set in, DejaVu Sans Mono, as the theme has none. It is bundled with the crate
+/// and registered with the assets the first time it is needed.
+#[derive(Debug, Default)]
+struct Monospace(Option<resources::Font>);
+
+impl Monospace {
+    const FONT: &'static [u8] = include_bytes!("../../resources/fonts/dejavu_sans_mono.ttf");
+
+    fn font(ctx: &mut Context) -> resources::Font {
+        if let Some(font) = &ctx.state().get_or_default::<Monospace>().0 {return font.clone();}
+        let font = ctx.assets.add_font(Self::FONT);
+        ctx.state().set(Monospace(Some(font.clone())));
+        font
+    }
+}
+
+/// The lines of a fenced code block, kept as typed on a darker background.
+#[derive(Debug, Component)]
+struct CodeBlock(Stack, RoundedRectangle, Text);
+impl OnEvent for CodeBlock {}
+
+impl CodeBlock {
+    fn new(ctx: &mut Context, code: &str, styles: Styles, max_w: f32) -> Self {
+        let (hp, vp) = (8.0, 8.0);
+        let max_w = max_w-(hp*2.0);
+        let mut text = Text::new(ctx, code, styles.text, styles.size*0.875, Align::Left);
+        text.text().spans[0].font = Monospace::font(ctx);
+        text.text().width = Some(max_w);
+        let layout = Stack(
+            Offset::Center, Offset::Center,
+            Size::custom(move |widths: Vec<(f32, f32)>| {
+                let size = (widths[1].1+(hp*2.)).min(max_w+(hp*2.));
+                (size, size)
+            }),
+            Size::custom(move |heights: Vec<(f32, f32)>| (heights[1].0+(vp*2.), heights[1].1+(vp*2.))),
+            Padding::default()
+        );
+        CodeBlock(layout, RoundedRectangle::new(0.0, 8.0, styles.code), text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pelican_ui::air::OrangeSecret;
+
+    fn plain(text: &str) -> (String, Emphasis) {(text.to_string(), Emphasis::Plain)}
+
+    #[test]
+    fn emphasis_markers() {
+        assert_eq!(Emphasis::runs("a *b* _c d_ `e`"), vec![
+            plain("a "), ("b".to_string(), Emphasis::Bold), plain(" "),
+            ("c d".to_string(), Emphasis::Italic), plain(" "), ("e".to_string(), Emphasis::Code),
+        ]);
+        assert_eq!(Emphasis::runs("(*b*)."), vec![plain("("), ("b".to_string(), Emphasis::Bold), plain(").")]);
+        assert_eq!(Emphasis::runs("2 * 3 * 4"), vec![plain("2 * 3 * 4")]);
+        assert_eq!(Emphasis::runs("snake_case_name"), vec![plain("snake_case_name")]);
+        assert_eq!(Emphasis::runs("**"), vec![plain("**")]);
+        assert!(Emphasis::runs("").is_empty());
+    }
+
+    #[test]
+    fn emphasis_does_not_nest() {
+        assert_eq!(Emphasis::runs("*bold _and italic_*"), vec![("bold _and italic_".to_string(), Emphasis::Bold)]);
+        assert_eq!(Emphasis::runs("`*not bold*`"), vec![("*not bold*".to_string(), Emphasis::Code)]);
+        assert_eq!(Emphasis::runs("_a_ *b*"), vec![("a".to_string(), Emphasis::Italic), plain(" "), ("b".to_string(), Emphasis::Bold)]);
+    }
+
+    #[test]
+    fn unclosed_markers_stay_as_typed() {
+        assert_eq!(Emphasis::runs("*open and _also"), vec![plain("*open and _also")]);
+        assert_eq!(Emphasis::runs("`code"), vec![plain("`code")]);
+        assert_eq!(Emphasis::runs("*a *b*"), vec![("a *b".to_string(), Emphasis::Bold)]);
+    }
+
+    #[test]
+    fn emphasis_skips_links() {
+        assert_eq!(Emphasis::runs("see https://example.com/_x_/y"), vec![plain("see https://example.com/_x_/y")]);
+        assert_eq!(Emphasis::runs("(www.example.com/*a*/b). _next_"), vec![
+            plain("(www.example.com/*a*/b). "), ("next".to_string(), Emphasis::Italic),
+        ]);
+        assert_eq!(Emphasis::runs("`me@example.com`"), vec![("me@example.com".to_string(), Emphasis::Code)]);
+        assert_eq!(Emphasis::runs("*https://example.com*"), vec![("https://example.com".to_string(), Emphasis::Bold)]);
+    }
+
+    #[test]
+    fn detects_urls() {
+        assert_eq!(Link::detect("https://example.com/a?b=c"), Some(Link::Url("https://example.com/a?b=c".to_string())));
+        assert_eq!(Link::detect("HTTP://example.com"), Some(Link::Url("HTTP://example.com".to_string())));
+        assert_eq!(Link::detect("www.example.com"), Some(Link::Url("https://www.example.com".to_string())));
+        assert_eq!(Link::detect("https://"), None);
+        assert_eq!(Link::detect("www."), None);
+        assert_eq!(Link::detect("example"), None);
+    }
+
+    #[test]
+    fn trailing_punctuation_stays_out_of_links() {
+        let (before, core, link, after) = MessageText::split_link("(https://example.com/path).").unwrap();
+        assert_eq!((before, core, after), ("(", "https://example.com/path", ")."));
+        assert_eq!(link, Link::Url("https://example.com/path".to_string()));
+
+        let (before, core, _, after) = MessageText::split_link("\"www.example.com\"? ").unwrap();
+        assert_eq!((before, core, after), ("\"", "www.example.com", "\"? "));
+
+        let (_, core, link, after) = MessageText::split_link("me@example.com,").unwrap();
+        assert_eq!((core, after), ("me@example.com", ","));
+        assert_eq!(link, Link::Email("me@example.com".to_string()));
+
+        assert!(MessageText::split_link("(nothing).").is_none());
+    }
+
+    #[test]
+    fn emails_next_to_urls() {
+        assert_eq!(Link::detect("me@example.com"), Some(Link::Email("me@example.com".to_string())));
+        assert_eq!(Link::detect("https://example.com/@me"), Some(Link::Url("https://example.com/@me".to_string())));
+        assert_eq!(Link::detect("https://me@example.com"), Some(Link::Url("https://me@example.com".to_string())));
+        assert_eq!(Link::detect("me@localhost"), None);
+        assert_eq!(Link::detect("@example.com"), None);
+        assert_eq!(Link::detect("me@@example.com"), None);
+        assert_eq!(Link::detect("me@example..com"), None);
+
+        let links = "mail me@example.com or see https://example.com".split_whitespace().filter_map(Link::detect).collect::<Vec<_>>();
+        assert_eq!(links, vec![Link::Email("me@example.com".to_string()), Link::Url("https://example.com".to_string())]);
+    }
+
+    #[test]
+    fn orange_names() {
+        let name = OrangeSecret::new().name();
+        assert_eq!(Link::detect(&name.to_string()), Some(Link::OrangeName(name)));
+    }
+
+    #[test]
+    fn invalid_orange_names_are_not_links() {
+        assert_eq!(Link::detect("orange_name:zz"), None);
+        assert_eq!(Link::detect("orange_name:"), None);
+        assert_eq!(Link::detect(&format!("orange_name:{}", "0".repeat(66))), None);
+        assert_eq!(Link::detect(&format!("orange_name:{}", "g".repeat(66))), None);
+        assert!(MessageText::split_link("(orange_name:zz).").is_none());
+    }
+}
@@ -1,9 +1,21 @@
-use pelican_ui::Context;
-use pelican_ui::air::Id;
+use pelican_ui::events::{OnEvent, Event, TickEvent};
+use pelican_ui::drawable::{Drawable, Component, Align};
+use pelican_ui::layout::{Area, SizeRequest, Layout};
+use pelican_ui::{Context, Component};
+use pelican_ui::air::{OrangeName, Id};
 use profiles::plugin::ProfilePlugin;
+use crate::components::Quote;
 use crate::plugin::MessagesPlugin;
-use crate::service::Message;
-use pelican_ui_std::{TextInput, ClearActiveInput};
+use uuid::Uuid;
+use crate::service::{Message, Rooms};
+use pelican_ui_std::{TextInput, ClearActiveInput, Column, Row, IconButton, Offset, Size, Padding, Text, TextStyle, NavigateEvent};
+use chrono::{DateTime, Utc, Duration};
+use pelican_ui::hardware::ImageOrientation;
+use std::sync::mpsc::{self, Receiver};
+
+/// The message the user is currently replying to, if any.
+#[derive(Debug, Default)]
+pub struct ReplyDraft(pub Option<(Id, Uuid)>);
 
 pub struct TextInputMessages;
 impl TextInputMessages {
@@ -14,7 +26,54 @@ impl TextInputMessages {
                 move |ctx: &mut Context, string: &mut String| {
                     if !string.is_empty() {
                         let me = ProfilePlugin::me(ctx).0;
-                        let message = Message::from(string.to_string(), me);
+                        let message = match ctx.state().get_mut_or_default::<ReplyDraft>().0.take_if(|(id, _)| *id == current_room_id) {
+                            Some((_, parent)) => Message::reply(string.to_string(), me, parent),
+                            None => Message::from(string.to_string(), me)
+                        };
+                        MessagesPlugin::create_message(ctx, current_room_id, message);
+                        ctx.trigger_event(ClearActiveInput);
+                    }
+                }
+            )),
+            true,
+        )
+    }
+
+    pub fn group_name(ctx: &mut Context, name: &str) -> TextInput {
+        TextInput::new(ctx, Some(name), Some("Group name"), "Group name...", None, TextInput::NO_ICON, false)
+    }
+
+    pub fn group_topic(ctx: &mut Context, topic: &str) -> TextInput {
+        TextInput::new(ctx, Some(topic), Some("Topic"), "What's this group about?", None, TextInput::NO_ICON, false)
+    }
+
+    pub fn room_title(ctx: &mut Context) -> TextInput {
+        TextInput::new(ctx, None, Some("Title"), "Room title...", None, TextInput::NO_ICON, false)
+    }
+
+    pub fn room_description(ctx: &mut Context) -> TextInput {
+        TextInput::new(ctx, None, Some("Description"), "What's this room about?", None, TextInput::NO_ICON, false)
+    }
+
+    pub fn room_tags(ctx: &mut Context) -> TextInput {
+        TextInput::new(ctx, None, Some("Tags"), "hiking, photography, ...", None, TextInput::NO_ICON, false)
+    }
+
+    pub fn report_reason(ctx: &mut Context) -> TextInput {
+        TextInput::new(ctx, None, Some("Reason"), "Why are you reporting this message?", None, TextInput::NO_ICON, false)
+    }
+
+    pub fn file_path(ctx: &mut Context) -> TextInput {
+        TextInput::new(ctx, None, Some("File"), "Path to the file...", None, TextInput::NO_ICON, false)
+    }
+
+    pub fn thread(ctx: &mut Context, current_room_id: Id, parent: Uuid) -> TextInput {
+        TextInput::new(ctx, None, None, "Reply...", None, 
+            Some(("send", 
+                move |ctx: &mut Context, string: &mut String| {
+                    if !string.is_empty() {
+                        let me = ProfilePlugin::me(ctx).0;
+                        let message = Message::in_thread(string.to_string(), me, parent);
                         MessagesPlugin::create_message(ctx, current_room_id, message);
                         ctx.trigger_event(ClearActiveInput);
                     }
@@ -23,4 +82,86 @@ impl TextInputMessages {
             true,
         )
     }
-}
\ No newline at end of file
+}
+
+/// Message input with the quoted message from the [`ReplyDraft`] attached above it.
+/// Shows who else is typing and publishes my own typing state while the input changes.
+/// Photos picked from the button beside the input are sent straight away.
+#[derive(Debug, Component)]
+pub struct ComposerMessages(Column, Option<Text>, Option<ReplyPreview>, ComposerInput, #[skip] Id, #[skip] String, #[skip] Option<DateTime<Utc>>, #[skip] Vec<OrangeName>, #[skip] Receiver<(Vec<u8>, ImageOrientation)>);
+
+impl OnEvent for ComposerMessages {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(TickEvent) = event.downcast_ref::<TickEvent>() {
+            let draft = ctx.state().get_or_default::<ReplyDraft>().0.filter(|(id, _)| *id == self.4).map(|(_, key)| key);
+            if draft.as_ref() != self.2.as_ref().map(|preview| &preview.3) {
+                let parent = draft.and_then(|key| ctx.state().get_mut_or_default::<Rooms>().message(self.4, key).cloned());
+                self.2 = parent.map(|parent| ReplyPreview::new(ctx, &parent));
+            }
+
+            let me = ProfilePlugin::me(ctx).0;
+            let typing = ctx.state().get_mut_or_default::<Rooms>().get(self.4).map(|room| room.4.clone()).unwrap_or_default();
+            let typing = typing.into_iter().filter(|name| *name != me).collect::<Vec<_>>();
+            if typing != self.7 {
+                self.1 = Self::typing(ctx, &typing);
+                self.7 = typing;
+            }
+
+            if let Ok((bytes, orientation)) = self.8.try_recv() {
+                MessagesPlugin::send_image(ctx, self.4, bytes, orientation);
+            }
+
+            let value = self.3.3.value().clone();
+            if value != self.5 {
+                let published = self.6.is_some_and(|at| Utc::now() - at < Self::TYPING_INTERVAL);
+                if !value.is_empty() && !published {
+                    MessagesPlugin::typing(ctx, self.4);
+                    self.6 = Some(Utc::now());
+                }
+                self.5 = value;
+            }
+        }
+        true
+    }
+}
+
+impl ComposerMessages {
+    /// How often my typing state is republished while I keep typing.
+    const TYPING_INTERVAL: Duration = Duration::seconds(3);
+
+    pub fn new(ctx: &mut Context, current_room_id: Id) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let photos = IconButton::input(ctx, "photos", move |ctx: &mut Context| ctx.hardware.open_photo_picker(sender.clone()));
+        let file = MessagesPlugin::FILES.then(|| IconButton::input(ctx, "add", |ctx: &mut Context| ctx.trigger_event(NavigateEvent(5))));
+        let input = ComposerInput(Row::new(8.0, Offset::Center, Size::Fit, Padding::default()), photos, file, TextInputMessages::new(ctx, current_room_id));
+        ComposerMessages(Column::new(8.0, Offset::Start, Size::Fit, Padding::default()), None, None, input, current_room_id, String::new(), None, Vec::new(), receiver)
+    }
+
+    fn typing(ctx: &mut Context, names: &[OrangeName]) -> Option<Text> {
+        let names = names.iter().map(|n| ProfilePlugin::username(ctx, n)).collect::<Vec<_>>();
+        let text = match names.as_slice() {
+            [] => return None,
+            [name] => format!("{} is typing…", name),
+            [a, b] => format!("{} and {} are typing…", a, b),
+            _ => "Several people are typing…".to_string(),
+        };
+        let text_size = ctx.theme.fonts.size.sm;
+        Some(Text::new(ctx, &text, TextStyle::Secondary, text_size, Align::Left))
+    }
+}
+
+#[derive(Debug, Component)]
+struct ComposerInput(Row, IconButton, Option<IconButton>, TextInput);
+impl OnEvent for ComposerInput {}
+
+#[derive(Debug, Component)]
+struct ReplyPreview(Row, Quote, IconButton, #[skip] Uuid);
+impl OnEvent for ReplyPreview {}
+
+impl ReplyPreview {
+    fn new(ctx: &mut Context, parent: &Message) -> Self {
+        let quote = Quote::new(ctx, parent, None);
+        let close = IconButton::close(ctx, |ctx: &mut Context| ctx.state().get_mut_or_default::<ReplyDraft>().0 = None);
+        ReplyPreview(Row::new(8.0, Offset::Center, Size::Fit, Padding::default()), quote, close, parent.id())
+    }
+}
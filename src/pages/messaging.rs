@@ -7,10 +7,11 @@ use profiles::pages::{UserAccount, AccountActions};
 use profiles::plugin::ProfilePlugin;
 use pelican_ui::air::{OrangeName, Id};
 
-use crate::components::{QuickDeselect, MessageType, ListItemMessages, ListItemGroupMessages, TextMessageGroup, TextInputMessages, HeaderMessages};
-use crate::events::{CreateMessageEvent, OpenAccountEvent, SetRoomEvent};
+use crate::components::{QuickDeselect, MessageType, ListItemMessages, ListItemGroupMessages, TextMessageGroup, TextInputMessages, FileCard, ComposerMessages, HeaderMessages, ButtonMessages, ReplyDraft, AvatarMessages, AvatarContentMessages, EncodedImageMessages};
+use crate::events::{CreateMessageEvent, OpenAccountEvent, SetRoomEvent, OpenMessageEvent, OpenThreadEvent, SaveMessageEvent, DeleteMessageEvent, ReactEvent, RetryMessageEvent, LeaveRoomEvent, RemoveMemberEvent, SaveGroupEvent, SetRoleEvent, OpenImageEvent, AttachFileEvent};
+use crate::pages::ReportMessage;
 use crate::plugin::MessagesPlugin;
-use crate::service::{RoomsRequest, Rooms, Message};
+use crate::service::{RoomsRequest, Rooms, Room, Member, Message, RoomMetadata, Role, PublicRooms, Attachment, Attachments, AttachmentLimit, Reports};
 
 use pelican_ui_std::{
     AppPage, Stack, Page,
@@ -20,15 +21,21 @@ use pelican_ui_std::{
     Button, ButtonState, Searchbar,
     Bumper, TextInput, Alert,
     NavigateEvent, ListItemGroup,
+    DataItem, Timestamp, QuickActions,
+    Avatar, AvatarContent, EncodedImage,
+    AspectRatioImage,
 };
+use pelican_ui::hardware::ImageOrientation;
+use std::sync::mpsc::{self, Receiver};
 
+use chrono::Local;
 use uuid::Uuid;
 
 // use crate::MSGPlugin;
 // use crate::msg::{CurrentRoom, CurrentProfile};
 
 #[derive(Component)]
-pub struct MessagesHome(Stack, Page, #[skip] Option<Id>, #[skip] Vec<(Id, Vec<OrangeName>, Vec<Message>)>, #[skip] AccountActions);
+pub struct MessagesHome(Stack, Page, #[skip] Option<Id>, #[skip] Vec<Room>, #[skip] AccountActions);
 
 impl AppPage for MessagesHome {
     fn has_nav(&self) -> bool { true }
@@ -54,7 +61,8 @@ impl MessagesHome {
         let new_message = Button::primary(ctx, "Create Message", |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
 
         let bumper = Bumper::single_button(ctx, new_message);
-        let rooms = ctx.state().get_or_default::<Rooms>().rooms();
+        let public = ctx.state().get_or_default::<PublicRooms>().clone();
+        let rooms = ctx.state().get_or_default::<Rooms>().private_rooms(&public);
         let text_size = ctx.theme.fonts.size.md;
         let instructions = ExpandableText::new(ctx, "No messages yet.\nGet started by messaging a friend.", TextStyle::Secondary, text_size, Align::Center, None);
         let group = ListItemGroupMessages::new(ctx, rooms.clone());
@@ -70,7 +78,8 @@ impl MessagesHome {
 impl OnEvent for MessagesHome {
     fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
         if let Some(TickEvent) = event.downcast_ref::<TickEvent>() {
-            let rooms = ctx.state().get_or_default::<Rooms>().clone().rooms();
+            let public = ctx.state().get_or_default::<PublicRooms>().clone();
+            let rooms = ctx.state().get_or_default::<Rooms>().private_rooms(&public);
             if self.3 != rooms {
                 if let Some(group) = self.1.content().find::<ListItemGroup>() {
                     *group = ListItemGroupMessages::new(ctx, rooms);
@@ -157,13 +166,10 @@ impl OnEvent for SelectRecipients {
                 let (plugin, ctx) = guard.get();
                 let me = ProfilePlugin::me(ctx).0;
                 if let Some((_, room)) = ctx.state().get::<Rooms>().expect("no rooms in state").0.iter().find(|(u, _)| *u == uuid) {
+                    plugin.request(RoomsRequest::Join(room.0, me));
                     self.1.content().find::<QuickDeselect>().unwrap().get_orange_names().unwrap().iter().for_each(|on| {
                         plugin.request(RoomsRequest::Share(room.0, on.clone()));
                     });
-
-
-                    let message = Message::invisible(me);
-                    plugin.request(RoomsRequest::CreateMessage(room.0, message));
                     self.3 = Some(room.0);
                     let nav = if room.1.len() > 1 {1} else {2};
                     ctx.trigger_event(NavigateEvent(nav));
@@ -182,7 +188,7 @@ impl OnEvent for SelectRecipients {
 }
 
 #[derive(Component)]
-pub struct DirectMessage(Stack, Page, #[skip] Id, #[skip] OrangeName, #[skip] Option<Box<dyn AppPage>>, #[skip] AccountActions, #[skip] bool);
+pub struct DirectMessage(Stack, Page, #[skip] Id, #[skip] OrangeName, #[skip] Option<Box<dyn AppPage>>, #[skip] AccountActions, #[skip] bool, #[skip] Option<Uuid>);
 
 impl AppPage for DirectMessage {
     fn has_nav(&self) -> bool { false }
@@ -190,6 +196,19 @@ impl AppPage for DirectMessage {
         match index {
             0 => Ok(self.4.take().unwrap_or(Box::new(MessagesHome::new(ctx, self.5)))),
             1 => Ok(Box::new(UserAccount::new(ctx, self.3.clone(), self.5.clone(), self))),
+            2 => {
+                let key = self.7.take().unwrap();
+                Ok(Box::new(MessageInfo::new(ctx, self.2, key, MessageType::Contact, self)))
+            },
+            3 => {
+                let key = self.7.take().unwrap();
+                Ok(Box::new(MessageThread::new(ctx, self.2, key, MessageType::Contact, self)))
+            },
+            4 => {
+                let key = self.7.take().unwrap();
+                Ok(Box::new(ImageViewer::new(ctx, self.2, key, self)))
+            },
+            5 => Ok(Box::new(AttachFile::new(ctx, self.2, self))),
             _ => Err(self),
         }
     }
@@ -203,14 +222,10 @@ impl std::fmt::Debug for DirectMessage {
 
 impl DirectMessage {
     pub fn new(ctx: &mut Context, room_id: Id, account_actions: AccountActions, account_return: Option<Box<dyn AppPage>>) -> Self {
-        let mut room = ctx.state().get_mut_or_default::<Rooms>().get(room_id);
-        let room = room.as_mut().unwrap();
-        room.2.iter_mut().filter(|m| !m.is_read()).for_each(|m| {println!("SETTING TO READ"); m.read(true);});
-        let mut room = room.clone();
-
-        room.2.retain(|m| *m.message() != "__system__joined");
+        MessagesPlugin::mark_read(ctx, room_id);
+        let room = ctx.state().get_mut_or_default::<Rooms>().get(room_id).unwrap().clone();
         let me = ProfilePlugin::me(ctx).0;
-        let orange_name = room.1.into_iter().filter(|orange_name| *orange_name != me).collect::<Vec<_>>().first().unwrap_or(&me).clone();
+        let orange_name = room.1.iter().map(Member::name).find(|orange_name| **orange_name != me).unwrap_or(&me).clone();
 
         let username = ProfilePlugin::username(ctx, &orange_name); //ctx.state().get_or_default::<Profiles>().0.get(&orange_name).unwrap().clone();
         let is_blocked = ProfilePlugin::has_blocked(ctx, &me, &orange_name);
@@ -220,7 +235,7 @@ impl DirectMessage {
             .then(|| format!("You blocked {}. Unblock to message.", username))
             .or_else(|| blocked_me.then(|| format!("{} has blocked you.", username)))
             .map(|msg| Box::new(Alert::new(ctx, msg.as_str())) as Box<dyn Drawable>)
-            .unwrap_or_else(|| Box::new(TextInputMessages::new(ctx, room.0)) as Box<dyn Drawable>);
+            .unwrap_or_else(|| Box::new(ComposerMessages::new(ctx, room.0)) as Box<dyn Drawable>);
 
         
         let offset = if room.2.is_empty() {Offset::Center} else {Offset::End};
@@ -230,33 +245,32 @@ impl DirectMessage {
                 let text = format!("No messages yet.\nSend {} the first message.", username);
                 Box::new(ExpandableText::new(ctx, &text, TextStyle::Secondary, text_size, Align::Center, None)) as Box<dyn Drawable>
             },
-            false => Box::new(TextMessageGroup::new(ctx, &room.2, MessageType::Contact)) as Box<dyn Drawable>
+            false => Box::new(TextMessageGroup::new(ctx, &room.2, &room.3, MessageType::Contact)) as Box<dyn Drawable>
         };
 
         let bumper = Bumper::new(ctx, vec![bumper]);
         let content = Content::new(ctx, offset, vec![content]);
         let header = HeaderMessages::new(ctx, vec![orange_name.clone()]);
-        DirectMessage(Stack::center(), Page::new(Some(header), content, Some(bumper)), room_id, orange_name, account_return, account_actions, true)
+        DirectMessage(Stack::center(), Page::new(Some(header), content, Some(bumper)), room_id, orange_name, account_return, account_actions, true, None)
     }
 }
 
 impl OnEvent for DirectMessage {
     fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
         if let Some(TickEvent) = event.downcast_ref::<TickEvent>() {
-            let mut room = ctx.state().get_mut_or_default::<Rooms>().get(self.2);
-            let room = room.as_mut().unwrap();
-            room.2.iter_mut().filter(|m| !m.is_read()).for_each(|m| {println!("SETTING TO READ"); m.read(true);});
-            let mut room = room.clone();
-
-            room.2.retain(|m| *m.message() != "__system__joined");
+            MessagesPlugin::mark_read(ctx, self.2);
+            let Some(room) = ctx.state().get_mut_or_default::<Rooms>().get(self.2).cloned() else {
+                ctx.trigger_event(NavigateEvent(0));
+                return true;
+            };
             if !room.2.is_empty() {
                 if let Some(group) = &mut self.1.content().find::<TextMessageGroup>() {
-                    if room.2.len() > group.count() {
-                        **group = TextMessageGroup::new(ctx, &room.2, MessageType::Contact);
+                    if *group.messages() != room.2 || *group.cursors() != room.3 {
+                        **group = TextMessageGroup::new(ctx, &room.2, &room.3, MessageType::Contact);
                     }
                 } else {
                     self.1.content().remove::<ExpandableText>();
-                    let group = Box::new(TextMessageGroup::new(ctx, &room.2, MessageType::Contact)) as Box<dyn Drawable>;
+                    let group = Box::new(TextMessageGroup::new(ctx, &room.2, &room.3, MessageType::Contact)) as Box<dyn Drawable>;
                     self.1.content().items().push(group);
                     *self.1.content().offset() = Offset::End;
                 }
@@ -264,7 +278,8 @@ impl OnEvent for DirectMessage {
             
             if self.6 {
                 println!("AUTHORS {:?}", room.1);
-                if let Some(orange_name) = room.1.into_iter().filter(|orange_name| *orange_name != ProfilePlugin::me(ctx).0).collect::<Vec<_>>().first() {
+                let me = ProfilePlugin::me(ctx).0;
+                if let Some(orange_name) = room.1.iter().map(Member::name).find(|orange_name| **orange_name != me) {
                     println!("NAME {:?}", orange_name);
                     self.6 = false;
                     *self.1.header() = Some(HeaderMessages::new(ctx, vec![orange_name.clone()]));
@@ -274,20 +289,49 @@ impl OnEvent for DirectMessage {
                     }
                 }
             }
+        } else if let Some(OpenMessageEvent(key)) = event.downcast_ref::<OpenMessageEvent>() {
+            self.7 = Some(*key);
+            ctx.trigger_event(NavigateEvent(2));
+        } else if let Some(OpenThreadEvent(key)) = event.downcast_ref::<OpenThreadEvent>() {
+            self.7 = Some(*key);
+            ctx.trigger_event(NavigateEvent(3));
+        } else if let Some(OpenImageEvent(key)) = event.downcast_ref::<OpenImageEvent>() {
+            self.7 = Some(*key);
+            ctx.trigger_event(NavigateEvent(4));
+        } else if let Some(ReactEvent(key, emoji)) = event.downcast_ref::<ReactEvent>() {
+            MessagesPlugin::toggle_reaction(ctx, self.2, *key, emoji.clone());
+        } else if let Some(RetryMessageEvent(key)) = event.downcast_ref::<RetryMessageEvent>() {
+            MessagesPlugin::retry_message(ctx, self.2, *key);
         }
         true
     }
 }
 
 #[derive(Component)]
-pub struct GroupMessage(Stack, Page, #[skip] Id, #[skip] AccountActions);
+pub struct GroupMessage(Stack, Page, #[skip] Id, #[skip] AccountActions, #[skip] Option<Uuid>, #[skip] RoomMetadata);
 
 impl AppPage for GroupMessage {
     fn has_nav(&self) -> bool { false }
-    fn navigate(self: Box<Self>, ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
+    fn navigate(mut self: Box<Self>, ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
         match index {
             0 => Ok(Box::new(MessagesHome::new(ctx, self.3))),
-            1 => Ok(Box::new(GroupInfo::new(ctx, self.2, self.3))),
+            1 => match ctx.state().get_mut_or_default::<Rooms>().get(self.2).cloned() {
+                Some(room) => Ok(Box::new(GroupInfo::new(ctx, room, self.3))),
+                None => Ok(Box::new(MessagesHome::new(ctx, self.3))),
+            },
+            2 => {
+                let key = self.4.take().unwrap();
+                Ok(Box::new(MessageInfo::new(ctx, self.2, key, MessageType::Group, self)))
+            },
+            3 => {
+                let key = self.4.take().unwrap();
+                Ok(Box::new(MessageThread::new(ctx, self.2, key, MessageType::Group, self)))
+            },
+            4 => {
+                let key = self.4.take().unwrap();
+                Ok(Box::new(ImageViewer::new(ctx, self.2, key, self)))
+            },
+            5 => Ok(Box::new(AttachFile::new(ctx, self.2, self))),
             _ => Err(self),
         }
     }
@@ -301,53 +345,139 @@ impl std::fmt::Debug for GroupMessage {
 
 impl GroupMessage {
     pub fn new(ctx: &mut Context, room_id: Id, account_actions: AccountActions) -> Self {
-        let mut room = ctx.state().get_mut_or_default::<Rooms>().get(room_id).unwrap().clone();
-        room.2.retain(|m| *m.message() != "__system__joined");
+        MessagesPlugin::mark_read(ctx, room_id);
+        let room = ctx.state().get_mut_or_default::<Rooms>().get(room_id).unwrap().clone();
         let offset = if room.2.is_empty() {Offset::Center} else {Offset::End};
         let text_size = ctx.theme.fonts.size.md;
         let content = match room.2.is_empty() {
             true => Box::new(ExpandableText::new(ctx, "No messages yet.\nSend the first message.", TextStyle::Secondary, text_size, Align::Center, None)) as Box<dyn Drawable>,
-            false => Box::new(TextMessageGroup::new(ctx, &room.2, MessageType::Group)) as Box<dyn Drawable>
+            false => Box::new(TextMessageGroup::new(ctx, &room.2, &room.3, MessageType::Group)) as Box<dyn Drawable>
         };
-        let input = TextInputMessages::new(ctx, room.0);
+        let input = ComposerMessages::new(ctx, room.0);
 
         let bumper = Bumper::new(ctx, vec![Box::new(input)]);
         let content = Content::new(ctx, offset, vec![content]);
-        let header = HeaderMessages::new(ctx, room.1.clone());
-        GroupMessage(Stack::center(), Page::new(Some(header), content, Some(bumper)), room_id, account_actions)
+        let header = HeaderMessages::group(ctx, room.1.iter().map(|m| m.name().clone()).collect(), &room.5);
+        GroupMessage(Stack::center(), Page::new(Some(header), content, Some(bumper)), room_id, account_actions, None, room.5)
     }
 }
 
 impl OnEvent for GroupMessage {
     fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
         if let Some(TickEvent) = event.downcast_ref::<TickEvent>() {
-            let mut room = ctx.state().get_mut_or_default::<Rooms>().get(self.2).unwrap().clone();
-            room.2.retain(|m| *m.message() != "__system__joined");
+            MessagesPlugin::mark_read(ctx, self.2);
+            let Some(room) = ctx.state().get_mut_or_default::<Rooms>().get(self.2).cloned() else {
+                ctx.trigger_event(NavigateEvent(0));
+                return true;
+            };
             if !room.2.is_empty() {
                 if let Some(group) = &mut self.1.content().find::<TextMessageGroup>() {
-                    if room.2.len() > group.count() {
-                        **group = TextMessageGroup::new(ctx, &room.2, MessageType::Group);
+                    if *group.messages() != room.2 || *group.cursors() != room.3 {
+                        **group = TextMessageGroup::new(ctx, &room.2, &room.3, MessageType::Group);
                     }
                 } else {
                     self.1.content().remove::<ExpandableText>();
-                    let group = Box::new(TextMessageGroup::new(ctx, &room.2, MessageType::Group)) as Box<dyn Drawable>;
+                    let group = Box::new(TextMessageGroup::new(ctx, &room.2, &room.3, MessageType::Group)) as Box<dyn Drawable>;
                     self.1.content().items().push(group);
                     *self.1.content().offset() = Offset::End;
                 }
             }
+            if room.5 != self.5 {
+                *self.1.header() = Some(HeaderMessages::group(ctx, room.1.iter().map(|m| m.name().clone()).collect(), &room.5));
+                self.5 = room.5;
+            }
+        } else if let Some(OpenMessageEvent(key)) = event.downcast_ref::<OpenMessageEvent>() {
+            self.4 = Some(*key);
+            ctx.trigger_event(NavigateEvent(2));
+        } else if let Some(OpenThreadEvent(key)) = event.downcast_ref::<OpenThreadEvent>() {
+            self.4 = Some(*key);
+            ctx.trigger_event(NavigateEvent(3));
+        } else if let Some(OpenImageEvent(key)) = event.downcast_ref::<OpenImageEvent>() {
+            self.4 = Some(*key);
+            ctx.trigger_event(NavigateEvent(4));
+        } else if let Some(ReactEvent(key, emoji)) = event.downcast_ref::<ReactEvent>() {
+            MessagesPlugin::toggle_reaction(ctx, self.2, *key, emoji.clone());
+        } else if let Some(RetryMessageEvent(key)) = event.downcast_ref::<RetryMessageEvent>() {
+            MessagesPlugin::retry_message(ctx, self.2, *key);
+        }
+        true
+    }
+}
+
+#[derive(Component)]
+pub struct MessageThread(Stack, Page, #[skip] Id, #[skip] Uuid, #[skip] MessageType, #[skip] Option<Uuid>, #[skip] Option<Box<dyn AppPage>>);
+
+impl AppPage for MessageThread {
+    fn has_nav(&self) -> bool { false }
+    fn navigate(mut self: Box<Self>, ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
+        match index {
+            0 => Ok(self.6.take().unwrap()),
+            2 => {
+                let key = self.5.take().unwrap();
+                let style = self.4;
+                Ok(Box::new(MessageInfo::new(ctx, self.2, key, style, self)))
+            },
+            4 => {
+                let key = self.5.take().unwrap();
+                Ok(Box::new(ImageViewer::new(ctx, self.2, key, self)))
+            },
+            _ => Err(self),
+        }
+    }
+}
+
+impl std::fmt::Debug for MessageThread {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MessageThread")
+    }
+}
+
+impl MessageThread {
+    pub fn new(ctx: &mut Context, room_id: Id, parent: Uuid, style: MessageType, on_exit: Box<dyn AppPage>) -> Self {
+        let thread = ctx.state().get_mut_or_default::<Rooms>().thread(room_id, parent);
+        let content = TextMessageGroup::thread(ctx, &thread, style);
+        let input = TextInputMessages::thread(ctx, room_id, parent);
+
+        let bumper = Bumper::new(ctx, vec![Box::new(input)]);
+        let content = Content::new(ctx, Offset::Start, vec![Box::new(content)]);
+        let back = IconButton::navigation(ctx, "left", move |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
+        let header = Header::stack(ctx, Some(back), "Thread", None);
+        MessageThread(Stack::center(), Page::new(Some(header), content, Some(bumper)), room_id, parent, style, None, Some(on_exit))
+    }
+}
+
+impl OnEvent for MessageThread {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(TickEvent) = event.downcast_ref::<TickEvent>() {
+            let thread = ctx.state().get_mut_or_default::<Rooms>().thread(self.2, self.3);
+            let group = self.1.content().find::<TextMessageGroup>().unwrap();
+            if *group.messages() != thread {
+                *group = TextMessageGroup::thread(ctx, &thread, self.4);
+            }
+        } else if let Some(OpenMessageEvent(key)) = event.downcast_ref::<OpenMessageEvent>() {
+            self.5 = Some(*key);
+            ctx.trigger_event(NavigateEvent(2));
+        } else if let Some(OpenImageEvent(key)) = event.downcast_ref::<OpenImageEvent>() {
+            self.5 = Some(*key);
+            ctx.trigger_event(NavigateEvent(4));
+        } else if let Some(ReactEvent(key, emoji)) = event.downcast_ref::<ReactEvent>() {
+            MessagesPlugin::toggle_reaction(ctx, self.2, *key, emoji.clone());
+        } else if let Some(RetryMessageEvent(key)) = event.downcast_ref::<RetryMessageEvent>() {
+            MessagesPlugin::retry_message(ctx, self.2, *key);
         }
         true
     }
 }
 
 #[derive(Component)]
-pub struct GroupInfo(Stack, Page, #[skip] Id, #[skip] Option<OrangeName>, #[skip] AccountActions);
+pub struct GroupInfo(Stack, Page, #[skip] Id, #[skip] Option<OrangeName>, #[skip] AccountActions, #[skip] Receiver<(Vec<u8>, ImageOrientation)>, #[skip] ButtonState, #[skip] Option<String>, #[skip] bool);
 
 impl AppPage for GroupInfo {
     fn has_nav(&self) -> bool { false }
     fn navigate(self: Box<Self>, ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
         match index {
-            0 => Ok(Box::new(GroupMessage::new(ctx, self.2, self.4))),
+            0 if ctx.state().get_mut_or_default::<Rooms>().get(self.2).is_some() => Ok(Box::new(GroupMessage::new(ctx, self.2, self.4))),
+            0 | 2 => Ok(Box::new(MessagesHome::new(ctx, self.4))),
             1 => Ok(Box::new(UserAccount::new(ctx, self.3.as_ref().unwrap().clone(), self.4.clone(), self))),
             _ => Err(self),
         }
@@ -361,12 +491,11 @@ impl std::fmt::Debug for GroupInfo {
 }
 
 impl GroupInfo {
-    pub fn new(ctx: &mut Context, room_id: Id, account_actions: AccountActions) -> Self {
-        let room = ctx.state().get_mut_or_default::<Rooms>().get(room_id).unwrap().clone();
+    pub fn new(ctx: &mut Context, room: Room, account_actions: AccountActions) -> Self {
         let me = ProfilePlugin::me(ctx).0;
-        let contacts = room.1.iter().filter(|orange| **orange != me).map(|orange_name| {
-            let new_profile = orange_name.clone();
-            ListItemMessages::contact(ctx, orange_name, move |ctx: &mut Context| {
+        let contacts = room.1.iter().filter(|member| *member.name() != me).map(|member| {
+            let new_profile = member.name().clone();
+            ListItemMessages::member(ctx, member, move |ctx: &mut Context| {
                 ctx.trigger_event(OpenAccountEvent(new_profile.clone()));
                 ctx.trigger_event(NavigateEvent(1));
             })
@@ -375,20 +504,373 @@ impl GroupInfo {
         let text_size = ctx.theme.fonts.size.md;
         let members = format!("This group has {} members.", contacts.len());
         let text = Text::new(ctx, &members, TextStyle::Secondary, text_size, Align::Center);
-        let content = Content::new(ctx, Offset::Start, vec![Box::new(text), Box::new(ListItemGroup::new(contacts))]);
+        let (sender, receiver) = mpsc::channel();
+        let avatar_content = AvatarContentMessages::group(ctx, &room.5);
+        let is_admin = Rooms::is_admin(&room, &me);
+
+        let mut items: Vec<Box<dyn Drawable>> = match is_admin {
+            true => {
+                let avatar = AvatarMessages::group_with_edit(ctx, avatar_content, sender);
+                let name = TextInputMessages::group_name(ctx, room.5.name().map(|n| n.as_str()).unwrap_or_default());
+                let topic = TextInputMessages::group_topic(ctx, room.5.topic().map(|t| t.as_str()).unwrap_or_default());
+                vec![Box::new(avatar), Box::new(name), Box::new(topic), Box::new(text), Box::new(ListItemGroup::new(contacts))]
+            },
+            false => vec![Box::new(AvatarMessages::room(ctx, avatar_content)), Box::new(text), Box::new(ListItemGroup::new(contacts))],
+        };
+
+        if is_admin {
+            let buttons = room.1.iter().filter(|member| *member.name() != me).flat_map(|member| {
+                let username = ProfilePlugin::username(ctx, member.name());
+                let (label, role) = match member.role() {
+                    Role::Admin => (format!("Remove {} as admin", username), Role::Member),
+                    Role::Member => (format!("Make {} admin", username), Role::Admin),
+                };
+                let (promoted, removed) = (member.name().clone(), member.name().clone());
+                vec![
+                    Button::secondary(ctx, None, &label, None, move |ctx: &mut Context| ctx.trigger_event(SetRoleEvent(promoted.clone(), role)), None),
+                    Button::secondary(ctx, None, &format!("Remove {}", username), None, move |ctx: &mut Context| ctx.trigger_event(RemoveMemberEvent(removed.clone())), None),
+                ]
+            }).collect::<Vec<_>>();
+            if !buttons.is_empty() {items.push(Box::new(QuickActions::new(buttons)));}
+        }
+
+        let content = Content::new(ctx, Offset::Start, items);
+        let leave = Button::close(ctx, "Leave group", |ctx: &mut Context| ctx.trigger_event(LeaveRoomEvent));
+        let bumper = match is_admin {
+            true => {
+                let save = Button::disabled(ctx, "Save", |ctx: &mut Context| ctx.trigger_event(SaveGroupEvent));
+                Bumper::double_button(ctx, leave, save)
+            },
+            false => Bumper::single_button(ctx, leave),
+        };
  
         let back = IconButton::navigation(ctx, "left", move |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
 
         let header = Header::stack(ctx, Some(back), "Group Message Info", None);
-        GroupInfo(Stack::center(), Page::new(Some(header), content, None), room_id, None, account_actions)
+        GroupInfo(Stack::center(), Page::new(Some(header), content, Some(bumper)), room.0, None, account_actions, receiver, ButtonState::Default, room.5.avatar().cloned(), is_admin)
+    }
+
+    /// The metadata as currently entered on this page.
+    fn metadata(&mut self) -> RoomMetadata {
+        let name = self.1.content().find_at::<TextInput>(1).unwrap().value().trim().to_string();
+        let topic = self.1.content().find_at::<TextInput>(2).unwrap().value().trim().to_string();
+        RoomMetadata::new((!name.is_empty()).then_some(name), (!topic.is_empty()).then_some(topic), self.7.clone())
     }
 }
 
 impl OnEvent for GroupInfo {
-    fn on_event(&mut self, _ctx: &mut Context, event: &mut dyn Event) -> bool {
-        if let Some(OpenAccountEvent(orange_name)) = event.downcast_ref::<OpenAccountEvent>() {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(TickEvent) = event.downcast_ref::<TickEvent>() {
+            let Some(current) = ctx.state().get_mut_or_default::<Rooms>().get(self.2).map(|room| room.5.clone()) else {
+                ctx.trigger_event(NavigateEvent(2));
+                return true;
+            };
+            if !self.8 {return true;}
+            if let Ok((bytes, orientation)) = self.5.try_recv() && let Some(base64_png) = EncodedImage::encode(bytes, orientation)
+                && let Some(image) = EncodedImageMessages::decode(ctx, &base64_png) {
+                self.1.content().find::<Avatar>().unwrap().set_content(AvatarContent::Image(image));
+                self.7 = Some(base64_png);
+            }
+
+            let changed = self.metadata() != current;
+            let button = self.1.bumper().as_mut().unwrap().find_at::<Button>(1).unwrap();
+            button.update_state(ctx, !changed, changed, &mut self.6);
+        } else if let Some(SaveGroupEvent) = event.downcast_ref::<SaveGroupEvent>() {
+            let metadata = self.metadata();
+            MessagesPlugin::update_metadata(ctx, self.2, metadata);
+        } else if let Some(OpenAccountEvent(orange_name)) = event.downcast_ref::<OpenAccountEvent>() {
             self.3 = Some(orange_name.clone());
+        } else if let Some(SetRoleEvent(orange_name, role)) = event.downcast_ref::<SetRoleEvent>() {
+            MessagesPlugin::set_role(ctx, self.2, orange_name.clone(), *role);
+            ctx.trigger_event(NavigateEvent(0));
+        } else if let Some(RemoveMemberEvent(orange_name)) = event.downcast_ref::<RemoveMemberEvent>() {
+            MessagesPlugin::remove_member(ctx, self.2, orange_name.clone());
+            ctx.trigger_event(NavigateEvent(0));
+        } else if let Some(LeaveRoomEvent) = event.downcast_ref::<LeaveRoomEvent>() {
+            MessagesPlugin::leave_room(ctx, self.2);
+            ctx.trigger_event(NavigateEvent(2));
         }
         true
     }
-}
\ No newline at end of file
+}
+
+#[derive(Component)]
+pub struct MessageInfo(Stack, Page, #[skip] Id, #[skip] Uuid, #[skip] Option<Box<dyn AppPage>>, #[skip] MessageType);
+
+impl AppPage for MessageInfo {
+    fn has_nav(&self) -> bool { false }
+    fn navigate(mut self: Box<Self>, ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
+        match index {
+            0 => Ok(self.4.take().unwrap()),
+            1 => {
+                let on_exit = self.4.take().unwrap();
+                let parent = ctx.state().get_mut_or_default::<Rooms>().message(self.2, self.3).and_then(|m| m.thread().copied()).unwrap_or(self.3);
+                Ok(Box::new(MessageThread::new(ctx, self.2, parent, self.5, on_exit)))
+            },
+            2 => {
+                let on_exit = self.4.take().unwrap();
+                Ok(Box::new(ReportMessage::new(ctx, self.2, self.3, on_exit)))
+            },
+            _ => Err(self),
+        }
+    }
+}
+
+impl std::fmt::Debug for MessageInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MessageInfo")
+    }
+}
+
+impl MessageInfo {
+    pub fn new(ctx: &mut Context, room_id: Id, key: Uuid, style: MessageType, on_exit: Box<dyn AppPage>) -> Self {
+        let message = ctx.state().get_mut_or_default::<Rooms>().message(room_id, key).unwrap().clone();
+        let me = ProfilePlugin::me(ctx).0;
+        let is_mine = *message.author() == me;
+
+        let mut items: Vec<Box<dyn Drawable>> = match is_mine {
+            true => vec![Box::new(TextInput::new(ctx, Some(message.message()), Some("Message"), "Message...", None, TextInput::NO_ICON, false))],
+            false => vec![Box::new(DataItem::new(ctx, None, "Message", Some(message.message()), None, None, None))]
+        };
+
+        let reactions = ButtonMessages::REACTIONS.iter().map(|emoji| {
+            let count = message.reactions().get(*emoji).map(|n| n.len()).unwrap_or_default();
+            let selected = message.has_reacted(&me, emoji);
+            let (key, emoji) = (key, emoji.to_string());
+            ButtonMessages::reaction(ctx, &emoji.clone(), count, selected, move |ctx: &mut Context| {
+                MessagesPlugin::toggle_reaction(ctx, room_id, key, emoji.clone());
+                ctx.trigger_event(NavigateEvent(0));
+            })
+        }).collect::<Vec<_>>();
+        items.push(Box::new(QuickActions::new(reactions)));
+
+        let quote = key;
+        let reply = Button::secondary(ctx, None, "Reply", None, move |ctx: &mut Context| {
+            ctx.state().set(ReplyDraft(Some((room_id, quote))));
+            ctx.trigger_event(NavigateEvent(0));
+        }, None);
+        let thread = Button::secondary(ctx, None, "Reply in thread", None, |ctx: &mut Context| ctx.trigger_event(NavigateEvent(1)), None);
+        items.push(Box::new(reply));
+        items.push(Box::new(thread));
+
+        if style == MessageType::Rooms && !is_mine && !message.is_system() {
+            items.extend(Self::moderation(ctx, room_id, &message));
+        }
+
+        message.reactions().iter().for_each(|(emoji, names)| {
+            let label = format!("{} · {}", emoji, names.len());
+            let names = names.iter().map(|n| ProfilePlugin::username(ctx, n)).collect::<Vec<_>>().join(", ");
+            items.push(Box::new(DataItem::new(ctx, None, &label, Some(&names), None, None, None)));
+        });
+
+        let mut written = *message.timestamp();
+        message.revisions().iter().enumerate().for_each(|(i, (text, replaced))| {
+            let time = Timestamp::new(written.with_timezone(&Local)).direct().unwrap();
+            let label = if i == 0 {format!("Original · {}", time)} else {format!("Edited · {}", time)};
+            items.push(Box::new(DataItem::new(ctx, None, &label, Some(text), None, None, None)));
+            written = *replaced;
+        });
+
+        let bumper = is_mine.then(|| {
+            let delete = Button::close(ctx, "Delete", |ctx: &mut Context| ctx.trigger_event(DeleteMessageEvent));
+            let save = Button::primary(ctx, "Save", |ctx: &mut Context| ctx.trigger_event(SaveMessageEvent));
+            Bumper::double_button(ctx, delete, save)
+        });
+
+        let content = Content::new(ctx, Offset::Start, items);
+        let back = IconButton::navigation(ctx, "left", move |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
+        let header = Header::stack(ctx, Some(back), "Message", None);
+        MessageInfo(Stack::center(), Page::new(Some(header), content, bumper), room_id, key, Some(on_exit), style)
+    }
+
+    /// What can be done about someone else's message in a public room.
+    /// Admins delete it, clear its reports and mute, ban or purge its author. Everyone else reports it.
+    fn moderation(ctx: &mut Context, room_id: Id, message: &Message) -> Vec<Box<dyn Drawable>> {
+        let me = ProfilePlugin::me(ctx).0;
+        let Some(room) = ctx.state().get_mut_or_default::<Rooms>().get(room_id).cloned() else {return Vec::new()};
+        if !Rooms::is_admin(&room, &me) {
+            let report = Button::secondary(ctx, None, "Report", None, |ctx: &mut Context| ctx.trigger_event(NavigateEvent(2)), None);
+            return vec![Box::new(report)];
+        }
+
+        let (key, author) = (message.id(), message.author().clone());
+        let reports = ctx.state().get_or_default::<Reports>().get(&room);
+        let mut items = reports.iter().filter(|r| r.message() == key).map(|report| {
+            let label = format!("Reported by {}", ProfilePlugin::username(ctx, report.reporter()));
+            let reason = Some(report.reason().as_str()).filter(|r| !r.is_empty()).unwrap_or("No reason given");
+            Box::new(DataItem::new(ctx, None, &label, Some(reason), None, None, None)) as Box<dyn Drawable>
+        }).collect::<Vec<_>>();
+
+        let mut buttons = vec![Button::secondary(ctx, None, "Delete message", None, move |ctx: &mut Context| {
+            MessagesPlugin::delete_message(ctx, room_id, key);
+            ctx.trigger_event(NavigateEvent(0));
+        }, None)];
+        if !items.is_empty() {
+            buttons.push(Button::secondary(ctx, None, "Dismiss reports", None, move |ctx: &mut Context| {
+                MessagesPlugin::dismiss_reports(ctx, room_id, key);
+                ctx.trigger_event(NavigateEvent(0));
+            }, None));
+        }
+        if !Rooms::is_admin(&room, &author) {
+            let username = ProfilePlugin::username(ctx, &author);
+            let (muted, banned) = (room.6.is_muted(&author), room.6.is_banned(&author));
+            let mute = if muted {format!("Unmute {}", username)} else {format!("Mute {}", username)};
+            let ban = if banned {format!("Unban {}", username)} else {format!("Ban {}", username)};
+            let (muting, banning, purging) = (author.clone(), author.clone(), author);
+            buttons.push(Button::secondary(ctx, None, &mute, None, move |ctx: &mut Context| {
+                MessagesPlugin::mute(ctx, room_id, muting.clone(), !muted);
+                ctx.trigger_event(NavigateEvent(0));
+            }, None));
+            buttons.push(Button::secondary(ctx, None, &ban, None, move |ctx: &mut Context| {
+                MessagesPlugin::ban(ctx, room_id, banning.clone(), !banned);
+                ctx.trigger_event(NavigateEvent(0));
+            }, None));
+            buttons.push(Button::secondary(ctx, None, &format!("Delete messages from {}", username), None, move |ctx: &mut Context| {
+                MessagesPlugin::purge(ctx, room_id, purging.clone());
+                ctx.trigger_event(NavigateEvent(0));
+            }, None));
+        }
+        items.push(Box::new(QuickActions::new(buttons)));
+        items
+    }
+}
+
+impl OnEvent for MessageInfo {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(SaveMessageEvent) = event.downcast_ref::<SaveMessageEvent>() {
+            let message = self.1.content().find::<TextInput>().unwrap().value().clone();
+            let current = ctx.state().get_mut_or_default::<Rooms>().message(self.2, self.3).map(|m| m.message().clone());
+            if !message.is_empty() && Some(&message) != current.as_ref() {
+                MessagesPlugin::edit_message(ctx, self.2, self.3, message);
+            }
+            ctx.trigger_event(NavigateEvent(0));
+        } else if let Some(DeleteMessageEvent) = event.downcast_ref::<DeleteMessageEvent>() {
+            MessagesPlugin::delete_message(ctx, self.2, self.3);
+            ctx.trigger_event(NavigateEvent(0));
+        }
+        true
+    }
+}
+
+/// A photo from a message at full size. Its thumbnail stands in until the full image has synced.
+#[derive(Component)]
+pub struct ImageViewer(Stack, Page, #[skip] Uuid, #[skip] bool, #[skip] Option<Box<dyn AppPage>>);
+
+impl AppPage for ImageViewer {
+    fn has_nav(&self) -> bool { false }
+    fn navigate(mut self: Box<Self>, _ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
+        match index {
+            0 => Ok(self.4.take().unwrap()),
+            _ => Err(self),
+        }
+    }
+}
+
+impl std::fmt::Debug for ImageViewer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ImageViewer")
+    }
+}
+
+impl ImageViewer {
+    const WIDTH: f32 = 360.0;
+
+    pub fn new(ctx: &mut Context, room_id: Id, key: Uuid, on_exit: Box<dyn AppPage>) -> Self {
+        let full = ctx.state().get_or_default::<Attachments>().get(key).cloned();
+        if full.is_none() {MessagesPlugin::load_attachment(ctx, key);}
+        let thumbnail = ctx.state().get_mut_or_default::<Rooms>().message(room_id, key).and_then(|m| m.attachment().cloned());
+        let image = full.clone().or(thumbnail.and_then(|attachment| match attachment {
+            Attachment::Image(thumbnail) => Some(thumbnail),
+            Attachment::File(..) => None,
+        }));
+        let item = match image {
+            Some(image) => Self::image(ctx, &image),
+            None => Self::notice(ctx, "This photo is no longer available."),
+        };
+        let content = Content::new(ctx, Offset::Center, vec![item]);
+        let back = IconButton::navigation(ctx, "left", move |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
+        let header = Header::stack(ctx, Some(back), "Photo", None);
+        ImageViewer(Stack::center(), Page::new(Some(header), content, None), key, full.is_some(), Some(on_exit))
+    }
+
+    fn image(ctx: &mut Context, data: &str) -> Box<dyn Drawable> {
+        match EncodedImageMessages::decode(ctx, data) {
+            Some(image) => Box::new(AspectRatioImage::new(image, (Self::WIDTH, Self::WIDTH))),
+            None => Self::notice(ctx, "This photo can't be shown."),
+        }
+    }
+
+    fn notice(ctx: &mut Context, text: &str) -> Box<dyn Drawable> {
+        let text_size = ctx.theme.fonts.size.md;
+        Box::new(ExpandableText::new(ctx, text, TextStyle::Secondary, text_size, Align::Center, None))
+    }
+}
+
+impl OnEvent for ImageViewer {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(TickEvent) = event.downcast_ref::<TickEvent>() && !self.3
+            && let Some(full) = ctx.state().get_or_default::<Attachments>().get(self.2).cloned() {
+            *self.1.content().items() = vec![Self::image(ctx, &full)];
+            self.3 = true;
+        }
+        true
+    }
+}
+
+/// Attaches a file from a path on this device, up to the `AttachmentLimit`. Only offered where `MessagesPlugin::FILES`.
+#[derive(Component)]
+pub struct AttachFile(Stack, Page, #[skip] Id, #[skip] Option<Box<dyn AppPage>>);
+
+impl AppPage for AttachFile {
+    fn has_nav(&self) -> bool { false }
+    fn navigate(mut self: Box<Self>, _ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
+        match index {
+            0 => Ok(self.3.take().unwrap()),
+            _ => Err(self),
+        }
+    }
+}
+
+impl std::fmt::Debug for AttachFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AttachFile")
+    }
+}
+
+impl AttachFile {
+    pub fn new(ctx: &mut Context, room_id: Id, on_exit: Box<dyn AppPage>) -> Self {
+        let limit = FileCard::size(ctx.state().get_or_default::<AttachmentLimit>().0 as u64);
+        let text_size = ctx.theme.fonts.size.md;
+        let instructions = ExpandableText::new(ctx, &format!("Files up to {} can be attached.", limit), TextStyle::Secondary, text_size, Align::Center, None);
+        let items: Vec<Box<dyn Drawable>> = vec![Box::new(TextInputMessages::file_path(ctx)), Box::new(instructions)];
+        let content = Content::new(ctx, Offset::Start, items);
+        let send = Button::primary(ctx, "Send file", |ctx: &mut Context| ctx.trigger_event(AttachFileEvent));
+        let bumper = Bumper::single_button(ctx, send);
+        let back = IconButton::navigation(ctx, "left", move |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
+        let header = Header::stack(ctx, Some(back), "Attach file", None);
+        AttachFile(Stack::center(), Page::new(Some(header), content, Some(bumper)), room_id, Some(on_exit))
+    }
+}
+
+impl OnEvent for AttachFile {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(AttachFileEvent) = event.downcast_ref::<AttachFileEvent>() {
+            let path = std::path::PathBuf::from(self.1.content().find::<TextInput>().unwrap().value().trim());
+            let name = path.file_name().map(|name| name.to_string_lossy().to_string());
+            let error = match (name, std::fs::metadata(&path)) {
+                (Some(name), Ok(metadata)) if metadata.is_file() && std::fs::File::open(&path).is_ok() => {
+                    match MessagesPlugin::send_file(ctx, self.2, name, path.clone(), metadata.len()) {
+                        true => None,
+                        false => Some("This file is too large to attach."),
+                    }
+                },
+                _ => Some("This file couldn't be opened."),
+            };
+            match error {
+                Some(error) => self.1.content().find::<TextInput>().unwrap().set_error(ctx, error),
+                None => ctx.trigger_event(NavigateEvent(0)),
+            }
+        }
+        true
+    }
+}
@@ -7,10 +7,11 @@ use profiles::pages::{AccountActions};
 use profiles::plugin::ProfilePlugin;
 use pelican_ui::air::{Id};
 
-use crate::components::{Cards, QuickDeselect, MessageType, ListItemMessages, TextMessageGroup, TextInputMessages, HeaderMessages};
-use crate::events::{CreateMessageEvent, SetRoomEvent};
+use crate::components::{Cards, RoomSearchResults, QuickDeselect, AvatarMessages, AvatarContentMessages, EncodedImageMessages, TextInputMessages, MessageType, ListItemMessages, TextMessageGroup, ComposerMessages, HeaderMessages};
+use crate::events::{PublishRoomEvent, SetRoomEvent, OpenMessageEvent, OpenThreadEvent, ReactEvent, RetryMessageEvent, JoinRoomEvent, LeaveRoomEvent, ReportMessageEvent, OpenImageEvent};
+use crate::pages::{MessageInfo, MessageThread, ImageViewer, AttachFile};
 use crate::plugin::MessagesPlugin;
-use crate::service::{RoomsRequest, Rooms, Message, PublicRooms};
+use crate::service::{Rooms, PublicRooms, RoomMetadata, Access, Reports};
 
 use pelican_ui_std::{
     AppPage, Stack, Page,
@@ -19,7 +20,11 @@ use pelican_ui_std::{
     Offset, ListItem, Content,
     Button, ButtonState, Searchbar,
     Bumper, TextInput, NavigateEvent, 
+    ListItemSelector, Avatar, AvatarContent, EncodedImage,
+    ListItemGroup, QuickActions, DataItem, Alert,
 };
+use pelican_ui::hardware::ImageOrientation;
+use std::sync::mpsc::{self, Receiver};
 
 use uuid::Uuid;
 
@@ -27,14 +32,15 @@ use uuid::Uuid;
 // use crate::msg::{CurrentRoom, CurrentProfile};
 
 #[derive(Component)] // () = Vec<(Id, Vec<OrangeName>, Vec<Message>)>
-pub struct RoomsHome(Stack, Page, #[skip] Option<Id>, #[skip] AccountActions);
+pub struct RoomsHome(Stack, Page, #[skip] Option<Id>, #[skip] AccountActions, #[skip] PublicRooms);
 
 impl AppPage for RoomsHome {
     fn has_nav(&self) -> bool { true }
     fn navigate(self: Box<Self>, ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
         match index {
-            0 => Ok(Box::new(SelectRoomsRecipients::new(ctx, self.3))),
+            0 => Ok(Box::new(CreateRoom::new(ctx, self.3))),
             1 => Ok(Box::new(RoomsMessage::new(ctx, self.2.unwrap(), self.3))),
+            2 => Ok(Box::new(RoomsSearch::new(ctx, self.3))),
             _ => Err(self),
         }
     }
@@ -53,30 +59,45 @@ impl RoomsHome {
         let new_message = Button::primary(ctx, "Create Room", |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
 
         let bumper = Bumper::single_button(ctx, new_message);
-        // let rooms = ctx.state().get_or_default::<Rooms>().rooms();
-        let rooms = PublicRooms::default();
-        let cards = Cards::new(ctx, rooms.inner().clone(), 1);
-
-        let content = Content::new(ctx, Offset::Start, vec![Box::new(cards) as Box<dyn Drawable>]);
-        RoomsHome(Stack::center(), Page::new(Some(header), content, Some(bumper)), None, account_actions)
+        let rooms = ctx.state().get_or_default::<PublicRooms>().clone();
+        let text_size = ctx.theme.fonts.size.md;
+        let instructions = ExpandableText::new(ctx, "No public rooms yet.\nGet started by creating one.", TextStyle::Secondary, text_size, Align::Center, None);
+        let cards = Cards::new(ctx, rooms.clone().inner());
+        let content = match rooms.clone().inner().is_empty() {
+            false => Content::new(ctx, Offset::Start, vec![Box::new(cards)]),
+            true => Content::new(ctx, Offset::Center, vec![Box::new(instructions)])
+        };
+        RoomsHome(Stack::center(), Page::new(Some(header), content, Some(bumper)), None, account_actions, rooms)
     }
 }
 
 impl OnEvent for RoomsHome {
-    fn on_event(&mut self, _ctx: &mut Context, event: &mut dyn Event) -> bool {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
         if let Some(TickEvent) = event.downcast_ref::<TickEvent>() {
-            // let rooms = ctx.state().get_or_default::<Rooms>().clone().rooms();
-            // if self.3 != rooms {
-            //     if let Some(group) = self.1.content().find::<ListItemGroup>() {
-            //         *group = ListItemRoomsMessages::new(ctx, rooms);
-            //     } else {
-            //         self.3 = rooms.clone();
-            //         self.1.content().remove::<ExpandableText>();
-            //         let group = ListItemRoomsMessages::new(ctx, rooms);
-            //         self.1.content().items().push(Box::new(group));
-            //         *self.1.content().offset() = Offset::Start;
-            //     }
-            // }
+            let rooms = ctx.state().get_or_default::<PublicRooms>().clone();
+            if self.4 != rooms {
+                self.4 = rooms.clone();
+                if let Some(cards) = self.1.content().find::<Cards>() {
+                    *cards = Cards::new(ctx, rooms.inner());
+                } else {
+                    self.1.content().remove::<ExpandableText>();
+                    let cards = Cards::new(ctx, rooms.inner());
+                    self.1.content().items().push(Box::new(cards));
+                    *self.1.content().offset() = Offset::Start;
+                }
+            }
+        } else if let Some(JoinRoomEvent(id)) = event.downcast_ref::<JoinRoomEvent>() {
+            match MessagesPlugin::can_join(ctx, *id) {
+                Ok(()) => {
+                    MessagesPlugin::join_room(ctx, *id);
+                    self.2 = Some(*id);
+                    ctx.trigger_event(NavigateEvent(1));
+                },
+                Err(error) => {
+                    self.1.content().remove::<Alert>();
+                    self.1.content().items().insert(0, Box::new(Alert::new(ctx, error)));
+                }
+            }
         } else if let Some(SetRoomEvent(id)) = event.downcast_ref::<SetRoomEvent>() {
             self.2 = Some(*id);
         }
@@ -86,14 +107,165 @@ impl OnEvent for RoomsHome {
 
 
 #[derive(Component)]
-pub struct SelectRoomsRecipients(Stack, Page, #[skip] ButtonState, #[skip] Option<Id>, #[skip] AccountActions, #[skip] Option<Uuid>, #[skip] bool);
+pub struct RoomsSearch(Stack, Page, #[skip] Option<Id>, #[skip] AccountActions);
+
+impl AppPage for RoomsSearch {
+    fn has_nav(&self) -> bool { false }
+    fn navigate(self: Box<Self>, ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
+        match index {
+            0 => Ok(Box::new(RoomsHome::new(ctx, self.3))),
+            1 => Ok(Box::new(RoomsMessage::new(ctx, self.2.unwrap(), self.3))),
+            _ => Err(self),
+        }
+    }
+}
+
+impl std::fmt::Debug for RoomsSearch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RoomsSearch")
+    }
+}
+
+impl RoomsSearch {
+    pub fn new(ctx: &mut Context, account_actions: AccountActions) -> Self {
+        let icon_button = None::<(&'static str, fn(&mut Context, &mut String))>;
+        let searchbar = Searchbar::new(TextInput::new(ctx, None, None, "Room name, topic or tag...", None, icon_button, false));
+
+        let rooms = ctx.state().get_or_default::<PublicRooms>().clone().inner();
+        let content = match rooms.is_empty() {
+            true => {
+                let text_size = ctx.theme.fonts.size.md;
+                Box::new(Text::new(ctx, "No rooms found.", TextStyle::Secondary, text_size, Align::Center)) as Box<dyn Drawable>
+            },
+            false => Box::new(RoomSearchResults::new(ctx, rooms)) as Box<dyn Drawable>
+        };
+
+        let content = Content::new(ctx, Offset::Start, vec![Box::new(searchbar), content]);
+        let back = IconButton::navigation(ctx, "left", |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
+        let header = Header::stack(ctx, Some(back), "Search rooms", None);
+        RoomsSearch(Stack::center(), Page::new(Some(header), content, None), None, account_actions)
+    }
+}
+
+impl OnEvent for RoomsSearch {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(JoinRoomEvent(id)) = event.downcast_ref::<JoinRoomEvent>() {
+            match MessagesPlugin::can_join(ctx, *id) {
+                Ok(()) => {
+                    MessagesPlugin::join_room(ctx, *id);
+                    self.2 = Some(*id);
+                    ctx.trigger_event(NavigateEvent(1));
+                },
+                Err(error) => {
+                    self.1.content().remove::<Alert>();
+                    self.1.content().items().insert(1, Box::new(Alert::new(ctx, error)));
+                }
+            }
+        }
+        true
+    }
+}
+
+
+/// First step of creating a public room: its details and who can join.
+/// Open rooms are published from here, invite-only rooms go on to pick their members.
+#[derive(Component)]
+pub struct CreateRoom(Stack, Page, #[skip] AccountActions, #[skip] Receiver<(Vec<u8>, ImageOrientation)>, #[skip] ButtonState, #[skip] Option<String>, #[skip] Option<Uuid>, #[skip] Option<Id>, #[skip] RoomMetadata);
+
+impl AppPage for CreateRoom {
+    fn has_nav(&self) -> bool { false }
+    fn navigate(self: Box<Self>, ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
+        match index {
+            0 => Ok(Box::new(RoomsHome::new(ctx, self.2))),
+            1 => Ok(Box::new(RoomsMessage::new(ctx, self.7.unwrap(), self.2))),
+            2 => Ok(Box::new(SelectRoomsRecipients::new(ctx, self.2, self.8))),
+            _ => Err(self),
+        }
+    }
+}
+
+impl std::fmt::Debug for CreateRoom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CreateRoom")
+    }
+}
+
+impl CreateRoom {
+    pub fn new(ctx: &mut Context, account_actions: AccountActions) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let avatar = AvatarMessages::group_with_edit(ctx, AvatarContentMessages::default(), sender);
+        let title = TextInputMessages::room_title(ctx);
+        let description = TextInputMessages::room_description(ctx);
+        let tags = TextInputMessages::room_tags(ctx);
+        let access = ListItemSelector::new(ctx,
+            ("Open", "Anyone can find and join", None),
+            ("Invite only", "Anyone can find it, admins add members", None),
+            None, None
+        );
+
+        let content = Content::new(ctx, Offset::Start, vec![Box::new(avatar), Box::new(title), Box::new(description), Box::new(tags), Box::new(access)]);
+        let back = IconButton::navigation(ctx, "left", |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
+        let header = Header::stack(ctx, Some(back), "Create room", None);
+        let button = Button::disabled(ctx, "Continue", move |ctx: &mut Context| ctx.trigger_event(PublishRoomEvent));
+
+        let bumper = Bumper::single_button(ctx, button);
+        CreateRoom(Stack::center(), Page::new(Some(header), content, Some(bumper)), account_actions, receiver, ButtonState::Default, None, None, None, RoomMetadata::default())
+    }
+
+    /// The room as currently entered on this page.
+    fn metadata(&mut self) -> RoomMetadata {
+        let title = self.1.content().find_at::<TextInput>(1).unwrap().value().trim().to_string();
+        let description = self.1.content().find_at::<TextInput>(2).unwrap().value().trim().to_string();
+        let tags = self.1.content().find_at::<TextInput>(3).unwrap().value().split(',').map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty()).collect();
+        let access = match self.1.content().find::<ListItemSelector>().unwrap().index() {
+            Some(1) => Access::InviteOnly,
+            _ => Access::Open,
+        };
+        RoomMetadata::new((!title.is_empty()).then_some(title), (!description.is_empty()).then_some(description), self.5.clone())
+            .with_tags(tags)
+            .with_access(access)
+    }
+}
+
+impl OnEvent for CreateRoom {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(TickEvent) = event.downcast_ref::<TickEvent>() {
+            if let Ok((bytes, orientation)) = self.3.try_recv() && let Some(base64_png) = EncodedImage::encode(bytes, orientation)
+                && let Some(image) = EncodedImageMessages::decode(ctx, &base64_png) {
+                self.1.content().find::<Avatar>().unwrap().set_content(AvatarContent::Image(image));
+                self.5 = Some(base64_png);
+            }
+
+            let error = self.6.is_some() || self.metadata().name().is_none();
+            let button = self.1.bumper().as_mut().unwrap().find::<Button>().unwrap();
+            button.update_state(ctx, error, !error, &mut self.4);
+
+            if let Some(uuid) = self.6 && let Some(id) = MessagesPlugin::published(ctx, uuid) {
+                MessagesPlugin::join_room(ctx, id);
+                self.7 = Some(id);
+                ctx.trigger_event(NavigateEvent(1));
+            }
+        } else if let Some(PublishRoomEvent) = event.downcast_ref::<PublishRoomEvent>() {
+            self.8 = self.metadata();
+            match self.8.access() {
+                Access::InviteOnly => ctx.trigger_event(NavigateEvent(2)),
+                Access::Open => self.6 = Some(MessagesPlugin::publish(ctx, self.8.clone())),
+            }
+        }
+        true
+    }
+}
+
+/// Last step of creating an invite-only room: pick who to add, then publish it.
+#[derive(Component)]
+pub struct SelectRoomsRecipients(Stack, Page, #[skip] ButtonState, #[skip] Option<Id>, #[skip] AccountActions, #[skip] Option<Uuid>, #[skip] RoomMetadata);
 
 impl AppPage for SelectRoomsRecipients {
     fn has_nav(&self) -> bool { false }
     fn navigate(self: Box<Self>, ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
         match index {
-            0 => Ok(Box::new(RoomsHome::new(ctx, self.4))),
-            // 1 => Ok(Box::new(RoomsMessage::new(ctx, self.3.unwrap(), self.4))),
+            0 => Ok(Box::new(CreateRoom::new(ctx, self.4))),
+            1 => Ok(Box::new(RoomsMessage::new(ctx, self.3.unwrap(), self.4))),
             _ => Err(self),
         }
     }
@@ -106,7 +278,7 @@ impl std::fmt::Debug for SelectRoomsRecipients {
 }
 
 impl SelectRoomsRecipients {
-    pub fn new(ctx: &mut Context, account_actions: AccountActions) -> Self {
+    pub fn new(ctx: &mut Context, account_actions: AccountActions, metadata: RoomMetadata) -> Self {
         let icon_button = None::<(&'static str, fn(&mut Context, &mut String))>;
         let searchbar = Searchbar::new(TextInput::new(ctx, None, None, "Profile name...", None, icon_button, false));
 
@@ -117,8 +289,6 @@ impl SelectRoomsRecipients {
             ListItemMessages::recipient(ctx, orange_name)
         }).collect::<Vec<ListItem>>();
 
-        // let recipients = vec![];
-        
         let content = match recipients.is_empty() {
             true => {
                 let text_size = ctx.theme.fonts.size.md;
@@ -130,11 +300,11 @@ impl SelectRoomsRecipients {
         let content = Content::new(ctx, Offset::Start, vec![Box::new(searchbar), content]);
         let back = IconButton::navigation(ctx, "left", |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
 
-        let header = Header::stack(ctx, Some(back), "Create message", None);
-        let button = Button::disabled(ctx, "Continue", move |ctx: &mut Context| ctx.trigger_event(CreateMessageEvent));
+        let header = Header::stack(ctx, Some(back), "Add members", None);
+        let button = Button::disabled(ctx, "Create room", move |ctx: &mut Context| ctx.trigger_event(PublishRoomEvent));
 
         let bumper = Bumper::single_button(ctx, button);
-        SelectRoomsRecipients(Stack::center(), Page::new(Some(header), content, Some(bumper)), ButtonState::Default, None, account_actions, None, false)
+        SelectRoomsRecipients(Stack::center(), Page::new(Some(header), content, Some(bumper)), ButtonState::Default, None, account_actions, None, metadata)
     }
 }
 
@@ -142,33 +312,19 @@ impl OnEvent for SelectRoomsRecipients {
     fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
         if let Some(TickEvent) = event.downcast_ref::<TickEvent>() {
             let error = self.1.content().find::<QuickDeselect>().map(|deselect| deselect.get_orange_names().is_none()).unwrap_or(true);
-            let error = error || self.6;
+            let error = error || self.5.is_some();
             let button = self.1.bumper().as_mut().unwrap().find::<Button>().unwrap();
             button.update_state(ctx, error, !error, &mut self.2);
 
-            if let Some(uuid) = self.5 {
-                // println!("ID was some {:?}", uuid);
-                let mut guard = ctx.get::<MessagesPlugin>();
-                let (plugin, ctx) = guard.get();
-                let me = ProfilePlugin::me(ctx).0;
-                if let Some((_, room)) = ctx.state().get::<Rooms>().expect("no rooms in state").0.iter().find(|(u, _)| *u == uuid) {
-                    self.1.content().find::<QuickDeselect>().unwrap().get_orange_names().unwrap().iter().for_each(|on| {
-                        plugin.request(RoomsRequest::Share(room.0, on.clone()));
-                    });
-
-                    let message = Message::invisible(me);
-                    plugin.request(RoomsRequest::CreateMessage(room.0, message));
-                    self.3 = Some(room.0);
-                    ctx.trigger_event(NavigateEvent(1));
-                }
+            if let Some(uuid) = self.5 && let Some(id) = MessagesPlugin::published(ctx, uuid) {
+                MessagesPlugin::join_room(ctx, id);
+                let invited = self.1.content().find::<QuickDeselect>().unwrap().get_orange_names().unwrap_or_default();
+                invited.into_iter().for_each(|orange_name| MessagesPlugin::invite(ctx, id, orange_name));
+                self.3 = Some(id);
+                ctx.trigger_event(NavigateEvent(1));
             }
-        } else if let Some(CreateMessageEvent) = event.downcast_ref::<CreateMessageEvent>() {
-            self.6 = true;
-            let mut guard = ctx.get::<MessagesPlugin>();
-            let plugin = guard.get().0;
-            let uuid = uuid::Uuid::new_v4();
-            plugin.request(RoomsRequest::CreateRoom(uuid));
-            self.5 = Some(uuid);
+        } else if let Some(PublishRoomEvent) = event.downcast_ref::<PublishRoomEvent>() {
+            self.5 = Some(MessagesPlugin::publish(ctx, self.6.clone()));
         }
         true
     }
@@ -176,14 +332,27 @@ impl OnEvent for SelectRoomsRecipients {
 
 
 #[derive(Component)]
-pub struct RoomsMessage(Stack, Page, #[skip] Id, #[skip] AccountActions);
+pub struct RoomsMessage(Stack, Page, #[skip] Id, #[skip] AccountActions, #[skip] Option<Uuid>);
 
 impl AppPage for RoomsMessage {
     fn has_nav(&self) -> bool { false }
-    fn navigate(self: Box<Self>, ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
+    fn navigate(mut self: Box<Self>, ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
         match index {
             0 => Ok(Box::new(RoomsHome::new(ctx, self.3))),
-            // 1 => Ok(Box::new(GroupInfo::new(ctx, self.2, self.3))),
+            1 => Ok(Box::new(RoomInfo::new(ctx, self.2, self.3))),
+            2 => {
+                let key = self.4.take().unwrap();
+                Ok(Box::new(MessageInfo::new(ctx, self.2, key, MessageType::Rooms, self)))
+            },
+            3 => {
+                let key = self.4.take().unwrap();
+                Ok(Box::new(MessageThread::new(ctx, self.2, key, MessageType::Rooms, self)))
+            },
+            4 => {
+                let key = self.4.take().unwrap();
+                Ok(Box::new(ImageViewer::new(ctx, self.2, key, self)))
+            },
+            5 => Ok(Box::new(AttachFile::new(ctx, self.2, self))),
             _ => Err(self),
         }
     }
@@ -197,41 +366,202 @@ impl std::fmt::Debug for RoomsMessage {
 
 impl RoomsMessage {
     pub fn new(ctx: &mut Context, room_id: Id, account_actions: AccountActions) -> Self {
-        let mut room = ctx.state().get_mut_or_default::<Rooms>().get(room_id).unwrap().clone();
-        room.2.retain(|m| *m.message() != "__system__joined");
+        let room = ctx.state().get_mut_or_default::<Rooms>().get(room_id).unwrap().clone();
         let offset = if room.2.is_empty() {Offset::Center} else {Offset::End};
         let text_size = ctx.theme.fonts.size.md;
         let content = match room.2.is_empty() {
             true => Box::new(ExpandableText::new(ctx, "No messages yet.\nSend the first message.", TextStyle::Secondary, text_size, Align::Center, None)) as Box<dyn Drawable>,
-            false => Box::new(TextMessageGroup::new(ctx, &room.2, MessageType::Rooms)) as Box<dyn Drawable>
+            false => Box::new(TextMessageGroup::new(ctx, &room.2, &room.3, MessageType::Rooms)) as Box<dyn Drawable>
         };
-        let input = TextInputMessages::new(ctx, room.0);
+        let input = ComposerMessages::new(ctx, room.0);
 
         let bumper = Bumper::new(ctx, vec![Box::new(input)]);
         let content = Content::new(ctx, offset, vec![content]);
-        let header = HeaderMessages::new(ctx, room.1.clone());
-        RoomsMessage(Stack::center(), Page::new(Some(header), content, Some(bumper)), room_id, account_actions)
+        let header = HeaderMessages::group(ctx, room.1.iter().map(|m| m.name().clone()).collect(), &room.5);
+        RoomsMessage(Stack::center(), Page::new(Some(header), content, Some(bumper)), room_id, account_actions, None)
     }
 }
 
 impl OnEvent for RoomsMessage {
     fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
         if let Some(TickEvent) = event.downcast_ref::<TickEvent>() {
-            let mut room = ctx.state().get_mut_or_default::<Rooms>().get(self.2).unwrap().clone();
-            room.2.retain(|m| *m.message() != "__system__joined");
+            let Some(room) = ctx.state().get_mut_or_default::<Rooms>().get(self.2).cloned() else {
+                ctx.trigger_event(NavigateEvent(0));
+                return true;
+            };
             if !room.2.is_empty() {
                 if let Some(group) = &mut self.1.content().find::<TextMessageGroup>() {
-                    if room.2.len() > group.count() {
-                        **group = TextMessageGroup::new(ctx, &room.2, MessageType::Rooms);
+                    if *group.messages() != room.2 || *group.cursors() != room.3 {
+                        **group = TextMessageGroup::new(ctx, &room.2, &room.3, MessageType::Rooms);
                     }
                 } else {
                     self.1.content().remove::<ExpandableText>();
-                    let group = Box::new(TextMessageGroup::new(ctx, &room.2, MessageType::Rooms)) as Box<dyn Drawable>;
+                    let group = Box::new(TextMessageGroup::new(ctx, &room.2, &room.3, MessageType::Rooms)) as Box<dyn Drawable>;
                     self.1.content().items().push(group);
                     *self.1.content().offset() = Offset::End;
                 }
             }
+        } else if let Some(OpenMessageEvent(key)) = event.downcast_ref::<OpenMessageEvent>() {
+            self.4 = Some(*key);
+            ctx.trigger_event(NavigateEvent(2));
+        } else if let Some(OpenThreadEvent(key)) = event.downcast_ref::<OpenThreadEvent>() {
+            self.4 = Some(*key);
+            ctx.trigger_event(NavigateEvent(3));
+        } else if let Some(OpenImageEvent(key)) = event.downcast_ref::<OpenImageEvent>() {
+            self.4 = Some(*key);
+            ctx.trigger_event(NavigateEvent(4));
+        } else if let Some(ReactEvent(key, emoji)) = event.downcast_ref::<ReactEvent>() {
+            MessagesPlugin::toggle_reaction(ctx, self.2, *key, emoji.clone());
+        } else if let Some(RetryMessageEvent(key)) = event.downcast_ref::<RetryMessageEvent>() {
+            MessagesPlugin::retry_message(ctx, self.2, *key);
+        }
+        true
+    }
+}
+
+/// Who is in a public room. Admins also see its reports and lift mutes and bans here.
+#[derive(Component)]
+pub struct RoomInfo(Stack, Page, #[skip] Id, #[skip] AccountActions, #[skip] Option<Uuid>);
+
+impl AppPage for RoomInfo {
+    fn has_nav(&self) -> bool { false }
+    fn navigate(self: Box<Self>, ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
+        match index {
+            0 => Ok(Box::new(RoomsMessage::new(ctx, self.2, self.3))),
+            1 => {
+                let room = Box::new(RoomsMessage::new(ctx, self.2, self.3));
+                Ok(Box::new(MessageInfo::new(ctx, self.2, self.4.unwrap(), MessageType::Rooms, room)))
+            },
+            2 => Ok(Box::new(RoomsHome::new(ctx, self.3))),
+            _ => Err(self),
+        }
+    }
+}
+
+impl std::fmt::Debug for RoomInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RoomInfo")
+    }
+}
+
+impl RoomInfo {
+    pub fn new(ctx: &mut Context, room_id: Id, account_actions: AccountActions) -> Self {
+        let room = ctx.state().get_mut_or_default::<Rooms>().get(room_id).unwrap().clone();
+        let me = ProfilePlugin::me(ctx).0;
+        let avatar_content = AvatarContentMessages::group(ctx, &room.5);
+        let mut items: Vec<Box<dyn Drawable>> = vec![Box::new(AvatarMessages::room(ctx, avatar_content))];
+
+        let text_size = ctx.theme.fonts.size.md;
+        if let Some(topic) = room.5.topic() {
+            items.push(Box::new(ExpandableText::new(ctx, topic, TextStyle::Primary, text_size, Align::Center, None)));
+        }
+
+        if Rooms::is_admin(&room, &me) {
+            let reports = ctx.state().get_or_default::<Reports>().get(&room);
+            let reports = reports.iter().filter_map(|report| {
+                let message = room.2.iter().find(|m| m.id() == report.message())?;
+                let key = report.message();
+                Some(ListItemMessages::report(ctx, report, message, move |ctx: &mut Context| ctx.trigger_event(OpenMessageEvent(key))))
+            }).collect::<Vec<ListItem>>();
+            let summary = match reports.len() {
+                0 => "No open reports.".to_string(),
+                1 => "1 open report.".to_string(),
+                count => format!("{} open reports.", count),
+            };
+            items.push(Box::new(Text::new(ctx, &summary, TextStyle::Secondary, text_size, Align::Center)));
+            if !reports.is_empty() {items.push(Box::new(ListItemGroup::new(reports)));}
+
+            let lifted = room.6.muted().iter().map(|name| (name, false)).chain(room.6.banned().iter().map(|name| (name, true)));
+            let buttons = lifted.map(|(name, ban)| {
+                let label = format!("{} {}", if ban {"Unban"} else {"Unmute"}, ProfilePlugin::username(ctx, name));
+                let name = name.clone();
+                Button::secondary(ctx, None, &label, None, move |ctx: &mut Context| {
+                    match ban {
+                        true => MessagesPlugin::ban(ctx, room_id, name.clone(), false),
+                        false => MessagesPlugin::mute(ctx, room_id, name.clone(), false),
+                    }
+                    ctx.trigger_event(NavigateEvent(0));
+                }, None)
+            }).collect::<Vec<_>>();
+            if !buttons.is_empty() {items.push(Box::new(QuickActions::new(buttons)));}
+        }
+
+        let members = match room.1.len() {
+            1 => "This room has 1 member.".to_string(),
+            count => format!("This room has {} members.", count),
+        };
+        items.push(Box::new(Text::new(ctx, &members, TextStyle::Secondary, text_size, Align::Center)));
+        let members = room.1.iter().map(|member| ListItemMessages::member(ctx, member, |_: &mut Context| {})).collect::<Vec<ListItem>>();
+        items.push(Box::new(ListItemGroup::new(members)));
+
+        let content = Content::new(ctx, Offset::Start, items);
+        let leave = Button::close(ctx, "Leave room", |ctx: &mut Context| ctx.trigger_event(LeaveRoomEvent));
+        let bumper = Bumper::single_button(ctx, leave);
+        let back = IconButton::navigation(ctx, "left", move |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
+        let header = Header::stack(ctx, Some(back), "Room Info", None);
+        RoomInfo(Stack::center(), Page::new(Some(header), content, Some(bumper)), room_id, account_actions, None)
+    }
+}
+
+impl OnEvent for RoomInfo {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(OpenMessageEvent(key)) = event.downcast_ref::<OpenMessageEvent>() {
+            self.4 = Some(*key);
+            ctx.trigger_event(NavigateEvent(1));
+        } else if let Some(LeaveRoomEvent) = event.downcast_ref::<LeaveRoomEvent>() {
+            MessagesPlugin::leave_room(ctx, self.2);
+            ctx.trigger_event(NavigateEvent(2));
+        }
+        true
+    }
+}
+
+/// Asks why a message is being reported, then sends the report to the room's admins.
+#[derive(Component)]
+pub struct ReportMessage(Stack, Page, #[skip] Id, #[skip] Uuid, #[skip] Option<Box<dyn AppPage>>);
+
+impl AppPage for ReportMessage {
+    fn has_nav(&self) -> bool { false }
+    fn navigate(mut self: Box<Self>, _ctx: &mut Context, index: usize) -> Result<Box<dyn AppPage>, Box<dyn AppPage>> { 
+        match index {
+            0 => Ok(self.4.take().unwrap()),
+            _ => Err(self),
+        }
+    }
+}
+
+impl std::fmt::Debug for ReportMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ReportMessage")
+    }
+}
+
+impl ReportMessage {
+    pub fn new(ctx: &mut Context, room_id: Id, key: Uuid, on_exit: Box<dyn AppPage>) -> Self {
+        let message = ctx.state().get_mut_or_default::<Rooms>().message(room_id, key).map(|m| m.message().clone()).unwrap_or_default();
+        let text_size = ctx.theme.fonts.size.md;
+        let instructions = ExpandableText::new(ctx, "The room's admins will see this report and who sent it.", TextStyle::Secondary, text_size, Align::Center, None);
+        let items: Vec<Box<dyn Drawable>> = vec![
+            Box::new(DataItem::new(ctx, None, "Message", Some(&message), None, None, None)),
+            Box::new(TextInputMessages::report_reason(ctx)),
+            Box::new(instructions),
+        ];
+        let content = Content::new(ctx, Offset::Start, items);
+        let send = Button::primary(ctx, "Send report", |ctx: &mut Context| ctx.trigger_event(ReportMessageEvent));
+        let bumper = Bumper::single_button(ctx, send);
+        let back = IconButton::navigation(ctx, "left", move |ctx: &mut Context| ctx.trigger_event(NavigateEvent(0)));
+        let header = Header::stack(ctx, Some(back), "Report message", None);
+        ReportMessage(Stack::center(), Page::new(Some(header), content, Some(bumper)), room_id, key, Some(on_exit))
+    }
+}
+
+impl OnEvent for ReportMessage {
+    fn on_event(&mut self, ctx: &mut Context, event: &mut dyn Event) -> bool {
+        if let Some(ReportMessageEvent) = event.downcast_ref::<ReportMessageEvent>() {
+            let reason = self.1.content().find::<TextInput>().unwrap().value().trim().to_string();
+            MessagesPlugin::report(ctx, self.2, self.3, reason);
+            ctx.trigger_event(NavigateEvent(0));
         }
         true
     }
-}
\ No newline at end of file
+}
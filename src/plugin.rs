@@ -1,10 +1,24 @@
-use pelican_ui::air::Id;
+use pelican_ui::air::{OrangeName, Id};
 use pelican_ui::runtime;
 use pelican_ui::{Context, Plugin};
+use profiles::plugin::ProfilePlugin;
 // use serde_json::{Value, json};
 // use std::hash::{DefaultHasher, Hasher, Hash};
 
-use crate::service::{Message, RoomsRequest, RoomsService};
+use uuid::Uuid;
+use crate::service::{Message, Delivery, RoomsRequest, RoomsService, RoomsSync, PublicRoomsSync, Rooms, RoomMetadata, Role, PublicRooms, Member, Access, Moderation, Report, Attachment, Attachments, AttachmentLimit, Blob};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::path::{Path, PathBuf};
+use chrono::Utc;
+use image::imageops::FilterType;
+use image::codecs::jpeg::JpegEncoder;
+use pelican_ui::hardware::ImageOrientation;
+use pelican_ui_std::EncodedImage;
+
+/// The longest side, in pixels, of a photo sent in a message.
+const MAX_IMAGE: u32 = 2048;
+/// The smallest a photo is shrunk to while making it fit in a single record.
+const MIN_IMAGE: u32 = 256;
 
 pub struct MessagesPlugin(runtime::Context);
 impl Plugin for MessagesPlugin {
@@ -13,13 +27,277 @@ impl Plugin for MessagesPlugin {
     }
 }
 impl MessagesPlugin {
+    /// Whether files can be attached and saved here. Phones have no folders to pick a file from or save it to,
+    /// so they show files that others send but leave sending and saving them to a computer.
+    pub const FILES: bool = cfg!(not(any(target_os = "ios", target_os = "android")));
+
     pub fn request(&mut self, request: RoomsRequest) {
         self.0.send::<RoomsService>(&request)
     }
 
     pub fn create_message(ctx: &mut Context, id: Id, message: Message) {
+        ctx.state().get_mut_or_default::<Rooms>().echo(id, message.clone());
         let mut guard = ctx.get::<MessagesPlugin>();
         let plugin = guard.get().0;
         plugin.request(RoomsRequest::CreateMessage(id, message));
     }
+
+    /// Sends a photo as its own message: a thumbnail first, then the full image as a blob.
+    pub fn send_image(ctx: &mut Context, id: Id, bytes: Vec<u8>, orientation: ImageOrientation) {
+        let (Some(full), Some(thumbnail)) = (Self::full_image(&bytes, &orientation), EncodedImage::encode(bytes, orientation)) else {return};
+        let me = ProfilePlugin::me(ctx).0;
+        let message = Message::from(String::new(), me.clone()).with_attachment(Attachment::Image(thumbnail));
+        let key = message.id();
+        ctx.state().get_mut_or_default::<Attachments>().insert(key, full.clone());
+        Self::create_message(ctx, id, message);
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::Upload(id, Blob::Image(key, me, full)));
+    }
+
+    /// The photo upright as a base64 JPEG that fits in a single record of `Blob::CHUNK_SIZE` bytes.
+    /// It starts at `MAX_IMAGE` on its longest side and steps down in quality, then halves in size, until it fits.
+    fn full_image(bytes: &[u8], orientation: &ImageOrientation) -> Option<String> {
+        let mut image = orientation.apply_to(image::load_from_memory(bytes).ok()?);
+        let mut side = MAX_IMAGE;
+        while side >= MIN_IMAGE {
+            if image.width().max(image.height()) > side {
+                image = image.resize(side, side, FilterType::Triangle);
+            }
+            let rgb = image.to_rgb8();
+            for quality in [85, 70, 55] {
+                let mut jpeg = Vec::new();
+                JpegEncoder::new_with_quality(&mut jpeg, quality).encode_image(&rgb).ok()?;
+                if jpeg.len() <= Blob::CHUNK_SIZE {return Some(STANDARD.encode(jpeg));}
+            }
+            side /= 2;
+        }
+        None
+    }
+
+    /// Attaches the file at `path` to a new message, uploaded in chunks after it straight from the file,
+    /// which has to stay where it is until then. Returns false when the file is over the `AttachmentLimit`.
+    pub fn send_file(ctx: &mut Context, id: Id, name: String, path: PathBuf, size: u64) -> bool {
+        if !Self::FILES || size > ctx.state().get_or_default::<AttachmentLimit>().0 as u64 {return false;}
+        let me = ProfilePlugin::me(ctx).0;
+        let message = Message::from(String::new(), me.clone()).with_attachment(Attachment::File(name, size));
+        let key = message.id();
+        Self::create_message(ctx, id, message);
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::UploadFile(id, key, me, path, size));
+        true
+    }
+
+    /// Asks for the full version of an attachment, which shows up in `Attachments` once it has fully arrived.
+    pub fn load_attachment(ctx: &mut Context, key: Uuid) {
+        if ctx.state().get_or_default::<Attachments>().get(key).is_some() {return;}
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.0.send::<RoomsSync>(&key)
+    }
+
+    /// Writes a file attachment that has fully arrived to the downloads folder, returning where it went.
+    pub fn save_file(ctx: &mut Context, key: Uuid, name: &str) -> Result<PathBuf, String> {
+        if !Self::FILES {return Err("Files can only be saved on a computer".to_string());}
+        let data = ctx.state().get_or_default::<Attachments>().get(key).cloned().ok_or("This file hasn't downloaded yet")?;
+        let bytes = STANDARD.decode(data).map_err(|_| "This file is damaged")?;
+        let folder = Self::downloads().ok_or("There's no folder to save to")?;
+        let path = folder.join(Path::new(name).file_name().unwrap_or("attachment".as_ref()));
+        std::fs::write(&path, bytes).map_err(|error| format!("Couldn't save this file: {}", error))?;
+        Ok(path)
+    }
+
+    /// The folder files are saved to: the desktop's download folder, else the Downloads folder in the home folder,
+    /// else the home folder itself.
+    fn downloads() -> Option<PathBuf> {
+        if let Some(folder) = std::env::var_os("XDG_DOWNLOAD_DIR").map(PathBuf::from) && folder.is_dir() {return Some(folder);}
+        let home = PathBuf::from(std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?);
+        let downloads = home.join("Downloads");
+        Some(if downloads.is_dir() {downloads} else {home})
+    }
+
+    pub fn retry_message(ctx: &mut Context, id: Id, key: Uuid) {
+        let rooms = ctx.state().get_mut_or_default::<Rooms>();
+        rooms.set_delivery(id, key, Delivery::Pending);
+        let Some(message) = rooms.message(id, key).cloned() else {return};
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::CreateMessage(id, message));
+    }
+
+    /// Lists a new public room. It shows up in `PublicRooms` under the returned uuid once it has synced.
+    pub fn publish(ctx: &mut Context, metadata: RoomMetadata) -> Uuid {
+        let uuid = Uuid::new_v4();
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::Publish(uuid, metadata));
+        uuid
+    }
+
+    pub fn edit_message(ctx: &mut Context, id: Id, key: Uuid, message: String) {
+        let me = ProfilePlugin::me(ctx).0;
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::EditMessage(id, key, me, message));
+    }
+
+    pub fn delete_message(ctx: &mut Context, id: Id, key: Uuid) {
+        let me = ProfilePlugin::me(ctx).0;
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::DeleteMessage(id, key, me));
+    }
+
+    /// Moves my read cursor for the room up to its newest message.
+    pub fn mark_read(ctx: &mut Context, id: Id) {
+        let me = ProfilePlugin::me(ctx).0;
+        let Some(room) = ctx.state().get_mut_or_default::<Rooms>().get(id) else {return};
+        let Some(latest) = room.2.last().map(|m| *m.timestamp()) else {return};
+        if room.3.get(&me).is_some_and(|read| *read >= latest) {return;}
+        room.3.insert(me.clone(), latest);
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::Read(id, me, latest));
+    }
+
+    pub fn typing(ctx: &mut Context, id: Id) {
+        let me = ProfilePlugin::me(ctx).0;
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::Typing(id, me));
+    }
+
+    pub fn toggle_reaction(ctx: &mut Context, id: Id, key: Uuid, emoji: String) {
+        let me = ProfilePlugin::me(ctx).0;
+        let reacted = ctx.state().get_mut_or_default::<Rooms>().message(id, key).is_some_and(|m| m.has_reacted(&me, &emoji));
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        match reacted {
+            true => plugin.request(RoomsRequest::Unreact(id, key, me, emoji)),
+            false => plugin.request(RoomsRequest::React(id, key, me, emoji)),
+        }
+    }
+
+    /// Whether I can be in a public room, or why not, to tell people before they try to join it.
+    pub fn can_join(ctx: &mut Context, id: Id) -> Result<(), &'static str> {
+        let me = ProfilePlugin::me(ctx).0;
+        let Some(public) = ctx.state().get_or_default::<PublicRooms>().get(id).cloned() else {return Err("This room is no longer listed")};
+        if public.moderation().is_banned(&me) {return Err("You've been banned from this room")}
+        if !public.is_member(&me) && public.metadata().access() == Access::InviteOnly {return Err("This room is invite-only")}
+        Ok(())
+    }
+
+    /// Joins a public room and adds it to my rooms straight away. Rooms I'm already in are left alone.
+    /// Returns whether I'm in the room, which I can't be when `can_join` says why not.
+    pub fn join_room(ctx: &mut Context, id: Id) -> bool {
+        if Self::can_join(ctx, id).is_err() {return false;}
+        let me = ProfilePlugin::me(ctx).0;
+        let Some(public) = ctx.state().get_or_default::<PublicRooms>().get(id).cloned() else {return false};
+        let rooms = ctx.state().get_mut_or_default::<Rooms>();
+        if rooms.get(id).is_none() {
+            let mut room = public.room();
+            if !public.is_member(&me) {room.1.push(Member::new(me.clone(), None));}
+            rooms.0.push((public.uuid(), room));
+        }
+        if public.is_member(&me) {return true;}
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.0.send::<PublicRoomsSync>(&id);
+        plugin.request(RoomsRequest::Join(id, me));
+        true
+    }
+
+    /// The id of the public room published under `uuid`, once it has synced.
+    pub fn published(ctx: &mut Context, uuid: Uuid) -> Option<Id> {
+        ctx.state().get_or_default::<PublicRooms>().clone().inner().iter().find(|room| room.uuid() == uuid).map(|room| room.id())
+    }
+
+    /// Adds a member to a room. In private rooms this also shares the room's keys with them.
+    pub fn invite(ctx: &mut Context, id: Id, name: OrangeName) {
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::Share(id, name));
+    }
+
+    /// Leaves the room and drops it from my rooms straight away.
+    pub fn leave_room(ctx: &mut Context, id: Id) {
+        let me = ProfilePlugin::me(ctx).0;
+        ctx.state().get_mut_or_default::<Rooms>().0.retain(|(_, room)| room.0 != id);
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::Leave(id, me));
+    }
+
+    pub fn remove_member(ctx: &mut Context, id: Id, name: OrangeName) {
+        let me = ProfilePlugin::me(ctx).0;
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::Remove(id, me, name));
+    }
+
+    pub fn set_role(ctx: &mut Context, id: Id, name: OrangeName, role: Role) {
+        let me = ProfilePlugin::me(ctx).0;
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::SetRole(id, me, name, role));
+    }
+
+    /// Mutes or unmutes a member. Muted members stay in the room but their messages are dropped.
+    pub fn mute(ctx: &mut Context, id: Id, name: OrangeName, muted: bool) {
+        let me = ProfilePlugin::me(ctx).0;
+        let record = match muted {
+            true => Moderation::Mute(name, me, Utc::now()),
+            false => Moderation::Unmute(name, me, Utc::now()),
+        };
+        Self::moderate(ctx, id, record);
+    }
+
+    /// Bans a member, removing them from the room and dropping anything they write, or lifts their ban.
+    pub fn ban(ctx: &mut Context, id: Id, name: OrangeName, banned: bool) {
+        let me = ProfilePlugin::me(ctx).0;
+        let record = match banned {
+            true => Moderation::Ban(name, me, Utc::now()),
+            false => Moderation::Unban(name, me, Utc::now()),
+        };
+        Self::moderate(ctx, id, record);
+    }
+
+    /// Deletes every message a member wrote in the room.
+    pub fn purge(ctx: &mut Context, id: Id, name: OrangeName) {
+        let me = ProfilePlugin::me(ctx).0;
+        Self::moderate(ctx, id, Moderation::Purge(name, me, Utc::now()));
+    }
+
+    /// Reports a message privately to the room's admins.
+    pub fn report(ctx: &mut Context, id: Id, key: Uuid, reason: String) {
+        let me = ProfilePlugin::me(ctx).0;
+        let admins = ctx.state().get_mut_or_default::<Rooms>().get(id).map(|room| {
+            room.1.iter().filter(|m| m.role() == Role::Admin).map(|m| m.name().clone()).collect()
+        }).unwrap_or_default();
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::Report(id, Report::new(key, me, reason), admins));
+    }
+
+    /// Clears the reports about a message.
+    pub fn dismiss_reports(ctx: &mut Context, id: Id, key: Uuid) {
+        let me = ProfilePlugin::me(ctx).0;
+        Self::moderate(ctx, id, Moderation::Dismiss(key, me, Utc::now()));
+    }
+
+    fn moderate(ctx: &mut Context, id: Id, record: Moderation) {
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::Moderate(id, record));
+    }
+
+    /// Applies the new metadata locally and publishes it to the room.
+    pub fn update_metadata(ctx: &mut Context, id: Id, metadata: RoomMetadata) {
+        let me = ProfilePlugin::me(ctx).0;
+        if let Some(room) = ctx.state().get_mut_or_default::<Rooms>().get(id) {room.5 = metadata.clone();}
+        let mut guard = ctx.get::<MessagesPlugin>();
+        let plugin = guard.get().0;
+        plugin.request(RoomsRequest::UpdateMetadata(id, me, metadata));
+    }
 }
@@ -1,36 +1,619 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::LazyLock;
 use std::time::Duration;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 
 use maverick_os::Cache;
 use pelican_ui::runtime::{Services, Service, ServiceList, ThreadContext, async_trait, self};
-use pelican_ui::{hardware, resources};
+use pelican_ui::{hardware, Context};
 use pelican_ui::State;
-use pelican_ui::air::{OrangeName, Id, Service as AirService, Protocol, Validation, ChildrenValidation, HeaderInfo, RecordPath, Permissions};
+use pelican_ui::air::{OrangeName, Id, Service as AirService, Protocol, Validation, ChildrenValidation, HeaderInfo, RecordPath, Permissions, PublicItem, Filter, Op};
+use pelican_ui::air::{Request as AirRequest, Response as AirResponse, Error as AirError, ValidationError};
+use air::storage::records::KeyGen;
 use pelican_ui_std::AvatarContent;
-use crate::components::AvatarContentMessages;
+use crate::components::{AvatarContentMessages, EncodedImageMessages};
 
-use std::collections::HashSet;
 use serde::{Serialize, Deserialize};
 use chrono::{Utc, DateTime};
 use uuid::Uuid;
+use base64::{engine::general_purpose::STANDARD, Engine};
 
+/// An earlier text of an edited message and the time it was replaced.
+pub type Revision = (String, DateTime<Utc>);
+
+/// The members who reacted to a message, grouped per emoji.
+pub type Reactions = BTreeMap<String, Vec<OrangeName>>;
+
+/// How far each member has read a room, as the time of the newest message they have seen.
+pub type ReadCursors = BTreeMap<OrangeName, DateTime<Utc>>;
+
+/// Local delivery state of a message I sent, shown until `RoomsSync` reads it back from AIR.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum Delivery {
+    Pending,
+    Sent,
+    Failed,
+}
+
+/// What a message records: text someone wrote, or a change to the room shown as a notice.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum MessageKind {
+    #[default]
+    Text,
+    /// The author joined, invited by the given member when known.
+    Joined(Option<OrangeName>),
+    Left,
+    /// The author removed the given member.
+    Removed(OrangeName),
+    /// The author renamed the room.
+    Renamed(String),
+    /// The author gave the given member a new role.
+    RoleChanged(OrangeName, Role),
+}
+
+/// The `bool` after the author is a legacy local read flag, kept so older records still decode.
+/// Records written before messages carried an id or kind are upgraded by `Message::upgrade`.
+/// A reply quotes the message it answers, while a message in a thread names the message the thread hangs off.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub struct Message(String, DateTime<Utc>, OrangeName, bool);
+pub struct Message(
+    String, DateTime<Utc>, OrangeName, bool,
+    #[serde(default)] Vec<Revision>,
+    #[serde(default)] bool,
+    #[serde(default)] Reactions,
+    #[serde(default)] Option<Uuid>,
+    #[serde(default)] Uuid,
+    #[serde(default)] MessageKind,
+    #[serde(skip)] Option<Delivery>,
+    #[serde(default)] Option<Attachment>,
+    #[serde(default)] Option<Uuid>,
+);
 impl Message {
     pub fn from(message: String, author: OrangeName) -> Self {
-        Message(message, Utc::now(), author, false)
+        Message(message, Utc::now(), author, false, Vec::new(), false, BTreeMap::new(), None, Uuid::new_v4(), MessageKind::Text, None, None, None)
+    }
+
+    pub fn reply(message: String, author: OrangeName, parent: Uuid) -> Self {
+        Message(message, Utc::now(), author, false, Vec::new(), false, BTreeMap::new(), Some(parent), Uuid::new_v4(), MessageKind::Text, None, None, None)
+    }
+
+    pub fn in_thread(message: String, author: OrangeName, parent: Uuid) -> Self {
+        Message(message, Utc::now(), author, false, Vec::new(), false, BTreeMap::new(), None, Uuid::new_v4(), MessageKind::Text, None, None, Some(parent))
+    }
+
+    pub fn system(author: OrangeName, kind: MessageKind) -> Self {
+        Message(String::new(), Utc::now(), author, true, Vec::new(), false, BTreeMap::new(), None, Uuid::new_v4(), kind, None, None, None)
     }
 
-    pub fn invisible(author: OrangeName) -> Self {
-        Message("__system__joined".to_string(), Utc::now(), author, true)
+    pub fn with_attachment(mut self, attachment: Attachment) -> Self {
+        self.11 = Some(attachment);
+        self
     }
 
     pub fn author(&self) -> &OrangeName {&self.2}
     pub fn timestamp(&self) -> &DateTime<Utc> {&self.1}
     pub fn message(&self) -> &String {&self.0}
-    pub fn is_read(&self) -> &bool {&self.3}
-    pub fn read(&mut self, status: bool) {self.3 = status}
+    pub fn id(&self) -> Uuid {self.8}
+    pub fn revisions(&self) -> &Vec<Revision> {&self.4}
+    pub fn is_edited(&self) -> bool {!self.4.is_empty()}
+    pub fn edited_at(&self) -> Option<&DateTime<Utc>> {self.4.last().map(|(_, t)| t)}
+    pub fn is_deleted(&self) -> bool {self.5}
+    pub fn reactions(&self) -> &Reactions {&self.6}
+    pub fn has_reacted(&self, name: &OrangeName, emoji: &str) -> bool {
+        self.6.get(emoji).is_some_and(|names| names.contains(name))
+    }
+    pub fn reply_to(&self) -> Option<&Uuid> {self.7.as_ref()}
+    pub fn thread(&self) -> Option<&Uuid> {self.12.as_ref()}
+    pub fn delivery(&self) -> Option<Delivery> {self.10}
+    pub fn kind(&self) -> &MessageKind {&self.9}
+    pub fn is_system(&self) -> bool {self.9 != MessageKind::Text}
+    /// What is attached to the message, gone once it is deleted.
+    pub fn attachment(&self) -> Option<&Attachment> {self.11.as_ref().filter(|_| !self.5)}
+
+    /// Brings a message decoded from an older record up to date.
+    /// A missing id is derived the same way by every member, and the old join marker becomes `MessageKind::Joined`.
+    /// Only the marker written by `Share` has the read flag set, so a member typing the same text still sends text.
+    fn upgrade(&mut self) {
+        if self.8.is_nil() {
+            let hash = Id::hash(&(self.2.clone(), self.1));
+            self.8 = Uuid::from_slice(&hash[..16]).unwrap();
+        }
+        if self.9 == MessageKind::Text && self.3 && self.0 == "__system__joined" {
+            self.0.clear();
+            self.9 = MessageKind::Joined(None);
+        }
+    }
+
+    fn edit(&mut self, message: String, timestamp: DateTime<Utc>) {
+        if self.5 {return;}
+        let previous = std::mem::replace(&mut self.0, message);
+        self.4.push((previous, timestamp));
+    }
+
+    fn delete(&mut self) {
+        self.0.clear();
+        self.4.clear();
+        self.5 = true;
+        self.6.clear();
+        self.11 = None;
+    }
+
+    fn react(&mut self, name: OrangeName, emoji: String) {
+        if self.5 {return;}
+        let names = self.6.entry(emoji).or_default();
+        if !names.contains(&name) {names.push(name);}
+    }
+
+    fn unreact_all(&mut self, name: &OrangeName) {
+        self.6.values_mut().for_each(|names| names.retain(|n| n != name));
+        self.6.retain(|_, names| !names.is_empty());
+    }
+
+    fn unreact(&mut self, name: &OrangeName, emoji: &str) {
+        if let Some(names) = self.6.get_mut(emoji) {
+            names.retain(|n| n != name);
+            if names.is_empty() {self.6.remove(emoji);}
+        }
+    }
+}
+
+/// Something attached to a message. Only a preview travels with the message;
+/// the full attachment is written after it in `Blob` records and kept in `Attachments`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum Attachment {
+    /// A photo, previewed by a thumbnail from `EncodedImage::encode`.
+    Image(String),
+    /// A file by its name and size in bytes.
+    File(String, u64),
+}
+
+impl Attachment {
+    /// What to call the attachment where there's no room to show it.
+    pub fn name(&self) -> &str {
+        match self {
+            Attachment::Image(_) => "Photo",
+            Attachment::File(name, _) => name,
+        }
+    }
+}
+
+/// Records stored beside a message that carry the full version of its attachment, keyed by the message's id
+/// and naming who wrote them. Only blobs that `fit` their message are kept.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Blob {
+    /// The photo as a base64 image of at most `CHUNK_SIZE` bytes.
+    Image(Uuid, OrangeName, String),
+    /// One of the given number of base64 pieces of a file, which `Attachments::sync` puts back together.
+    Chunk(Uuid, OrangeName, u32, u32, String),
+}
+
+impl Blob {
+    /// The bytes of a photo or of a file in each `Chunk`, small enough for a single AIR record.
+    pub const CHUNK_SIZE: usize = 256 * 1024;
+
+    /// How many chunks carry a file of `size` bytes. Even an empty file has one.
+    fn count(size: u64) -> u32 {
+        size.div_ceil(Self::CHUNK_SIZE as u64).max(1) as u32
+    }
+
+    /// Reads the chunk at `index` of a file of `size` bytes, unless the file is gone or has changed size since.
+    fn read(path: &Path, index: u32, size: u64) -> Option<String> {
+        let mut file = File::open(path).ok()?;
+        if file.metadata().ok()?.len() != size {return None;}
+        let start = index as u64 * Self::CHUNK_SIZE as u64;
+        let mut chunk = vec![0; size.saturating_sub(start).min(Self::CHUNK_SIZE as u64) as usize];
+        file.seek(SeekFrom::Start(start)).ok()?;
+        file.read_exact(&mut chunk).ok()?;
+        Some(STANDARD.encode(chunk))
+    }
+
+    /// Whether the blob can carry the attachment of `message`: written by its author, of the attachment's kind
+    /// and no larger than a record, or for a chunk, cut the way `UploadFile` cuts a file of the attachment's size.
+    fn fits(&self, message: &Message) -> bool {
+        if message.id() != self.key() || message.author() != self.author() {return false;}
+        match (self, message.attachment()) {
+            (Blob::Image(.., data), Some(Attachment::Image(_))) => STANDARD.decode(data).is_ok_and(|bytes| bytes.len() <= Self::CHUNK_SIZE),
+            (Blob::Chunk(_, _, index, count, data), Some(Attachment::File(_, size))) => {
+                let length = size.saturating_sub(*index as u64 * Self::CHUNK_SIZE as u64).min(Self::CHUNK_SIZE as u64);
+                *count == Self::count(*size) && index < count && STANDARD.decode(data).is_ok_and(|bytes| bytes.len() as u64 == length)
+            },
+            _ => false,
+        }
+    }
+
+    fn key(&self) -> Uuid {
+        match self {
+            Blob::Image(key, ..) | Blob::Chunk(key, ..) => *key,
+        }
+    }
+
+    fn author(&self) -> &OrangeName {
+        match self {
+            Blob::Image(_, author, _) | Blob::Chunk(_, author, ..) => author,
+        }
+    }
+}
+
+/// The largest file in bytes that can be attached to a message. Apps can set their own in the state.
+#[derive(Clone, Copy, Debug)]
+pub struct AttachmentLimit(pub usize);
+
+impl Default for AttachmentLimit {
+    fn default() -> Self {
+        AttachmentLimit(25 * 1024 * 1024)
+    }
+}
+
+/// What `Attachments::sync` stores for a blob.
+enum Arrival {
+    /// A photo, kept as it is.
+    Whole(String),
+    /// The piece of a file at an index, with the number of pieces when it is the last to arrive.
+    Piece(u32, String, Option<u32>),
+}
+
+/// The full attachments synced so far, by the id of their message, as base64.
+/// They are kept out of `Rooms`, which pages clone all the time, and stored one per cache entry.
+#[derive(Clone, Debug, Default)]
+pub struct Attachments(BTreeMap<Uuid, String>);
+
+impl Attachments {
+    pub fn get(&self, key: Uuid) -> Option<&String> {
+        self.0.get(&key)
+    }
+
+    pub fn insert(&mut self, key: Uuid, data: String) {
+        self.0.insert(key, data);
+    }
+
+    /// Stores the blobs a sync just read, which have already been checked to `fit` their message.
+    /// Chunks wait in the cache, noted in `chunks`, until every piece of their file has arrived.
+    /// Nothing is put in the state here: pages ask for the attachments they show with `MessagesPlugin::load_attachment`.
+    async fn sync(cache: &mut Cache, stored: &mut BTreeSet<Uuid>, chunks: &mut BTreeMap<Uuid, BTreeSet<u32>>, blobs: Vec<Blob>) {
+        for blob in blobs {
+            let key = blob.key();
+            let data = match Self::arrive(stored, chunks, blob) {
+                None => continue,
+                Some(Arrival::Whole(data)) => data,
+                Some(Arrival::Piece(index, data, count)) => {
+                    cache.set(&format!("Attachment{}.{}", key, index), &data).await;
+                    let Some(count) = count else {continue};
+                    let mut pieces = Vec::new();
+                    for index in 0..count {
+                        pieces.push(cache.get(&format!("Attachment{}.{}", key, index)).await);
+                        cache.set(&format!("Attachment{}.{}", key, index), &String::new()).await;
+                    }
+                    Self::assemble(pieces)
+                },
+            };
+            cache.set(&format!("Attachment{}", key), &data).await;
+        }
+    }
+
+    /// Notes a blob that has arrived and says what to store for it.
+    /// The first blob for an attachment, or for each piece of a file, is the one kept; later ones give `None`.
+    fn arrive(stored: &mut BTreeSet<Uuid>, chunks: &mut BTreeMap<Uuid, BTreeSet<u32>>, blob: Blob) -> Option<Arrival> {
+        let key = blob.key();
+        if stored.contains(&key) {return None;}
+        match blob {
+            Blob::Image(.., data) => {
+                stored.insert(key);
+                Some(Arrival::Whole(data))
+            },
+            Blob::Chunk(_, _, index, count, data) => {
+                let received = chunks.entry(key).or_default();
+                if !received.insert(index) {return None;}
+                let complete = received.len() >= count as usize;
+                if complete {
+                    chunks.remove(&key);
+                    stored.insert(key);
+                }
+                Some(Arrival::Piece(index, data, complete.then_some(count)))
+            },
+        }
+    }
+
+    /// Puts the pieces of a file back together, in order.
+    fn assemble(pieces: Vec<String>) -> String {
+        STANDARD.encode(pieces.into_iter().flat_map(|piece| STANDARD.decode(piece).unwrap_or_default()).collect::<Vec<_>>())
+    }
+
+    /// An attachment stored by either sync, if it has fully arrived.
+    async fn load(cache: &mut Cache, key: Uuid) -> Option<String> {
+        let data: String = cache.get(&format!("Attachment{}", key)).await;
+        (!data.is_empty()).then_some(data)
+    }
+}
+
+/// Records stored beside the messages of a room that change an earlier message or the room itself.
+/// A `Delete` is a tombstone: the original record stays, but every member drops its content.
+/// Edits and deletes name who made them and only apply to that member's own messages.
+/// The newest `Metadata` replaces any earlier one. `TypingState` is kept in a single record per member
+/// that is overwritten in place; the `Typing` records older versions appended no longer decode and are skipped.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RoomEvent {
+    Edit(Uuid, String, DateTime<Utc>, OrangeName),
+    Delete(Uuid, DateTime<Utc>, OrangeName),
+    React(Uuid, OrangeName, String),
+    Unreact(Uuid, OrangeName, String),
+    Read(OrangeName, DateTime<Utc>),
+    TypingState(OrangeName, DateTime<Utc>),
+    Metadata(OrangeName, RoomMetadata),
+}
+
+impl RoomEvent {
+    fn apply(self, messages: &mut [Message], cursors: &mut ReadCursors) {
+        match self {
+            RoomEvent::Edit(key, message, timestamp, name) => {
+                if let Some(m) = messages.iter_mut().find(|m| m.id() == key && *m.author() == name) {
+                    m.edit(message, timestamp);
+                }
+            },
+            RoomEvent::Delete(key, _, name) => {
+                if let Some(m) = messages.iter_mut().find(|m| m.id() == key && *m.author() == name) {
+                    m.delete();
+                }
+            },
+            RoomEvent::React(key, name, emoji) => {
+                if let Some(m) = messages.iter_mut().find(|m| m.id() == key) {
+                    m.react(name, emoji);
+                }
+            },
+            RoomEvent::Unreact(key, name, emoji) => {
+                if let Some(m) = messages.iter_mut().find(|m| m.id() == key) {
+                    m.unreact(&name, &emoji);
+                }
+            },
+            RoomEvent::Read(name, timestamp) => {
+                let cursor = cursors.entry(name).or_insert(timestamp);
+                *cursor = timestamp.max(*cursor);
+            },
+            RoomEvent::TypingState(..) | RoomEvent::Metadata(..) => {}
+        }
+    }
+}
+
+/// Who can join a public room: anyone who finds it, or only the people an admin adds.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum Access {
+    #[default]
+    Open,
+    InviteOnly,
+}
+
+/// A group's custom name, topic and avatar. The avatar is a base64 PNG from `EncodedImage`.
+/// Public rooms also carry tags to be found by and who may join them.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct RoomMetadata(Option<String>, Option<String>, Option<String>, #[serde(default)] Vec<String>, #[serde(default)] Access);
+impl RoomMetadata {
+    pub fn new(name: Option<String>, topic: Option<String>, avatar: Option<String>) -> Self {
+        RoomMetadata(name, topic, avatar, Vec::new(), Access::Open)
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.3 = tags;
+        self
+    }
+
+    pub fn with_access(mut self, access: Access) -> Self {
+        self.4 = access;
+        self
+    }
+
+    pub fn name(&self) -> Option<&String> {self.0.as_ref()}
+    pub fn topic(&self) -> Option<&String> {self.1.as_ref()}
+    pub fn avatar(&self) -> Option<&String> {self.2.as_ref()}
+    pub fn tags(&self) -> &Vec<String> {&self.3}
+    pub fn access(&self) -> Access {self.4}
+}
+
+/// What a member may do in a group. Only admins rename it, change its settings, and add, remove or promote members.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum Role {
+    Admin,
+    #[default]
+    Member,
+}
+
+/// A member of a room, who invited them and when they joined. The member who created the room has no inviter.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Member(OrangeName, Option<OrangeName>, DateTime<Utc>, #[serde(default)] Role);
+impl Member {
+    pub fn new(name: OrangeName, inviter: Option<OrangeName>) -> Self {
+        Member(name, inviter, Utc::now(), Role::Member)
+    }
+
+    pub fn name(&self) -> &OrangeName {&self.0}
+    pub fn inviter(&self) -> Option<&OrangeName> {self.1.as_ref()}
+    pub fn joined(&self) -> &DateTime<Utc> {&self.2}
+    pub fn role(&self) -> Role {self.3}
+}
+
+/// Records stored beside the messages of a room that change who is in it.
+/// A `Leave` names the member and who removed them, which is the member themselves when they left.
+/// A `Role` names the member, their new role and the admin who changed it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Membership {
+    Join(Member),
+    Leave(OrangeName, OrangeName, DateTime<Utc>),
+    Role(OrangeName, Role, OrangeName, DateTime<Utc>),
+}
+
+impl Membership {
+    /// Applies the record if its author was allowed to make it.
+    /// The first member is the group's admin, and the longest-standing member takes over when the last admin goes.
+    /// The last admin can leave but can't be made a member, as that would hand the room to someone they didn't pick.
+    fn apply(self, members: &mut Vec<Member>) {
+        match self {
+            Membership::Join(member) => {
+                if member.1.as_ref().is_none_or(|by| Self::is_admin(members, by)) {Self::admit(members, member);}
+            },
+            Membership::Leave(name, by, _) => {
+                if by == name || Self::is_admin(members, &by) {
+                    members.retain(|m| m.0 != name);
+                }
+            },
+            Membership::Role(name, role, by, _) => {
+                let last = role != Role::Admin && members.iter().filter(|m| m.3 == Role::Admin).all(|m| m.0 == name);
+                if Self::is_admin(members, &by) && !last && let Some(member) = members.iter_mut().find(|m| m.0 == name) {
+                    member.3 = role;
+                }
+            },
+        }
+        if !members.iter().any(|m| m.3 == Role::Admin) && let Some(first) = members.first_mut() {
+            first.3 = Role::Admin;
+        }
+    }
+
+    /// Who made the change: the inviter, or the member themselves when they joined on their own.
+    fn by(&self) -> &OrangeName {
+        match self {
+            Membership::Join(member) => member.1.as_ref().unwrap_or(&member.0),
+            Membership::Leave(_, by, _) | Membership::Role(_, _, by, _) => by,
+        }
+    }
+
+    fn admit(members: &mut Vec<Member>, mut member: Member) {
+        if members.iter().any(|m| m.0 == member.0) {return;}
+        if members.is_empty() {member.3 = Role::Admin;}
+        members.push(member);
+    }
+
+    fn is_admin(members: &[Member], name: &OrangeName) -> bool {
+        members.iter().any(|m| m.0 == *name && m.3 == Role::Admin)
+    }
+}
+
+/// A member's report of a message to the room's admins: the message, who reported it, why and when.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Report(Uuid, OrangeName, String, DateTime<Utc>);
+impl Report {
+    pub fn new(message: Uuid, reporter: OrangeName, reason: String) -> Self {
+        Report(message, reporter, reason, Utc::now())
+    }
+
+    pub fn message(&self) -> Uuid {self.0}
+    pub fn reporter(&self) -> &OrangeName {&self.1}
+    pub fn reason(&self) -> &String {&self.2}
+    pub fn timestamp(&self) -> &DateTime<Utc> {&self.3}
+}
+
+/// The reports members sent me about rooms I'm an admin of, with the room each is about.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Reports(Vec<(Id, Report)>);
+impl Reports {
+    /// The open reports about messages in the room by its members, leaving out those its admins dismissed.
+    pub fn get(&self, room: &Room) -> Vec<Report> {
+        self.0.iter().filter(|(id, r)| {
+            *id == room.0 && room.2.iter().any(|m| m.id() == r.0) && room.1.iter().any(|m| m.0 == r.1) && !room.6.is_dismissed(r)
+        }).map(|(_, r)| r.clone()).collect()
+    }
+
+    /// Keeps a report, once per message and reporter.
+    fn insert(&mut self, room: Id, report: Report) {
+        if !self.0.iter().any(|(id, r)| *id == room && r.0 == report.0 && r.1 == report.1) {self.0.push((room, report));}
+    }
+}
+
+/// Records stored beside the messages of a room that admins keep it in order with.
+/// Each names the member acted on and the admin who acted. A `Purge` deletes every message the member wrote,
+/// and a `Dismiss` clears the reports made about a message until then. Reports themselves never enter the room:
+/// see `RoomsRequest::Report`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Moderation {
+    Mute(OrangeName, OrangeName, DateTime<Utc>),
+    Unmute(OrangeName, OrangeName, DateTime<Utc>),
+    Ban(OrangeName, OrangeName, DateTime<Utc>),
+    Unban(OrangeName, OrangeName, DateTime<Utc>),
+    Purge(OrangeName, OrangeName, DateTime<Utc>),
+    Dismiss(Uuid, OrangeName, DateTime<Utc>),
+}
+
+impl Moderation {
+    /// Applies the record if its author was allowed to make it. Only admins moderate, and never another admin.
+    /// Banning also removes the member from the room and takes down what they already wrote: their messages
+    /// are deleted and their reactions dropped, as `RoomModeration::drops` only stops records read after the ban.
+    fn apply(self, moderation: &mut RoomModeration, members: &mut Vec<Member>, messages: &mut [Message]) {
+        match self {
+            Moderation::Dismiss(key, by, timestamp) => {
+                if Membership::is_admin(members, &by) {
+                    let dismissed = moderation.2.entry(key).or_insert(timestamp);
+                    *dismissed = (*dismissed).max(timestamp);
+                }
+            },
+            Moderation::Mute(ref name, ref by, _) | Moderation::Unmute(ref name, ref by, _) | Moderation::Ban(ref name, ref by, _) |
+            Moderation::Unban(ref name, ref by, _) | Moderation::Purge(ref name, ref by, _)
+                if !Membership::is_admin(members, by) || Membership::is_admin(members, name) => {},
+            Moderation::Mute(name, ..) => {
+                if !moderation.0.contains(&name) {moderation.0.push(name);}
+            },
+            Moderation::Unmute(name, ..) => moderation.0.retain(|n| *n != name),
+            Moderation::Ban(name, ..) => {
+                members.retain(|m| m.0 != name);
+                moderation.0.retain(|n| *n != name);
+                messages.iter_mut().filter(|m| *m.author() == name && !m.is_system()).for_each(Message::delete);
+                messages.iter_mut().for_each(|m| m.unreact_all(&name));
+                if !moderation.1.contains(&name) {moderation.1.push(name);}
+            },
+            Moderation::Unban(name, ..) => moderation.1.retain(|n| *n != name),
+            Moderation::Purge(name, ..) => {
+                messages.iter_mut().filter(|m| *m.author() == name && !m.is_system()).for_each(Message::delete);
+            },
+        }
+    }
+
+    fn by(&self) -> &OrangeName {
+        match self {
+            Moderation::Mute(_, by, _) | Moderation::Unmute(_, by, _) | Moderation::Ban(_, by, _) |
+            Moderation::Unban(_, by, _) | Moderation::Purge(_, by, _) | Moderation::Dismiss(_, by, _) => by,
+        }
+    }
+}
+
+/// Who is muted and banned in a room, and when the reports about each message were last dismissed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct RoomModeration(Vec<OrangeName>, Vec<OrangeName>, BTreeMap<Uuid, DateTime<Utc>>);
+impl RoomModeration {
+    pub fn muted(&self) -> &Vec<OrangeName> {&self.0}
+    pub fn banned(&self) -> &Vec<OrangeName> {&self.1}
+    pub fn is_muted(&self, name: &OrangeName) -> bool {self.0.contains(name)}
+    pub fn is_banned(&self, name: &OrangeName) -> bool {self.1.contains(name)}
+    pub fn is_dismissed(&self, report: &Report) -> bool {self.2.get(&report.0).is_some_and(|dismissed| report.3 <= *dismissed)}
+
+    /// Whether to drop a record by `author`: anything from a banned member, their joining again,
+    /// and the messages of a muted one.
+    fn drops(&self, author: &OrangeName, record: &RoomRecord) -> bool {
+        self.is_banned(author) || match record {
+            RoomRecord::Message(message) => !message.is_system() && self.is_muted(author),
+            RoomRecord::Membership(Membership::Join(member)) => self.is_banned(&member.0),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RoomRecord {
+    Message(Message),
+    Event(RoomEvent),
+    Membership(Membership),
+    Moderation(Moderation),
+    Blob(Blob),
+}
+
+impl RoomRecord {
+    /// Who the record says wrote it.
+    fn author(&self) -> Option<&OrangeName> {
+        match self {
+            RoomRecord::Message(message) => Some(message.author()),
+            RoomRecord::Event(RoomEvent::React(_, name, _) | RoomEvent::Unreact(_, name, _) | RoomEvent::Read(name, _) |
+                RoomEvent::TypingState(name, _) | RoomEvent::Metadata(name, _) | RoomEvent::Edit(.., name) | RoomEvent::Delete(.., name)) => Some(name),
+            RoomRecord::Membership(record) => Some(record.by()),
+            RoomRecord::Moderation(record) => Some(record.by()),
+            RoomRecord::Blob(blob) => Some(blob.author()),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Default, Clone, Debug)]
@@ -40,17 +623,91 @@ impl Rooms {
     pub fn rooms(&self) -> Vec<Room> {
         self.0.clone().into_iter().map(|(_, r)| r).collect()
     }
+    /// My rooms without the public rooms I joined.
+    pub fn private_rooms(&self, public: &PublicRooms) -> Vec<Room> {
+        self.rooms().into_iter().filter(|r| public.get(r.0).is_none()).collect()
+    }
     pub fn get(&mut self, id: Id) -> Option<&mut Room> {
         self.0.iter_mut().find(|(_, i)| *i.0 == *id).map(|(_, r)| r)
     }
+    pub fn message(&mut self, id: Id, message_id: Uuid) -> Option<&Message> {
+        self.get(id).and_then(|r| r.2.iter().find(|m| m.id() == message_id))
+    }
+    /// Finds a message by id in any room, along with the id of its room.
+    pub fn find_message(&self, message_id: Uuid) -> Option<(Id, &Message)> {
+        self.0.iter().find_map(|(_, r)| r.2.iter().find(|m| m.id() == message_id).map(|m| (r.0, m)))
+    }
+    /// Shows a message I just sent before `RoomsSync` finds it.
+    pub fn echo(&mut self, id: Id, mut message: Message) {
+        message.10 = Some(Delivery::Pending);
+        if let Some(room) = self.get(id) {room.2.push(message);}
+    }
+    pub fn set_delivery(&mut self, id: Id, message_id: Uuid, delivery: Delivery) {
+        if let Some(m) = self.get(id).and_then(|r| r.2.iter_mut().find(|m| m.id() == message_id && m.10.is_some())) {
+            m.10 = Some(delivery);
+        }
+    }
+    /// Shows the messages still in the outbox again after a restart, as the echoes made when they were sent are gone.
+    /// The delivery travels beside each message, since it isn't part of what a message serializes.
+    fn restore(&mut self, unsent: Vec<(Id, Message, Delivery)>) {
+        for (id, message, delivery) in unsent {
+            let key = message.id();
+            if self.message(id, key).is_some() {continue;}
+            self.echo(id, message);
+            self.set_delivery(id, key, delivery);
+        }
+    }
+    /// Carries local echoes over from `previous` until the synced rooms contain them.
+    fn keep_unsent(&mut self, previous: &Rooms) {
+        for (uuid, room) in self.0.iter_mut() {
+            let Some((_, old)) = previous.0.iter().find(|(u, _)| u == uuid) else {continue};
+            let unsent = old.2.iter().filter(|m| m.10.is_some() && !room.2.iter().any(|n| n.id() == m.id())).cloned().collect::<Vec<_>>();
+            room.2.extend(unsent);
+        }
+    }
+    /// Carries the public rooms in `previous` over, since `RoomsSync` only knows my private rooms.
+    fn keep_public(&mut self, previous: &Rooms, public: &PublicRooms) {
+        self.0.extend(previous.0.iter().filter(|(_, r)| public.get(r.0).is_some()).cloned());
+    }
+    pub fn is_unread(room: &Room, me: &OrangeName) -> bool {
+        room.2.iter().rev().find(|m| !m.is_system()).is_some_and(|m| {
+            m.author() != me && room.3.get(me).is_none_or(|read| read < m.timestamp())
+        })
+    }
+    pub fn thread(&mut self, id: Id, message_id: Uuid) -> Vec<Message> {
+        self.get(id).map(|r| r.2.iter().filter(|m| m.id() == message_id || m.thread() == Some(&message_id)).cloned().collect()).unwrap_or_default()
+    }
+
+    /// Rebuilds the members of a room from before `Membership` records, from its notices and authors.
+    /// Anyone could add members back then, so joins are admitted whoever invited them.
+    fn legacy_members(messages: &[Message]) -> Vec<Member> {
+        let mut members: Vec<Member> = Vec::new();
+        for m in messages {
+            match m.kind() {
+                MessageKind::Joined(inviter) => Membership::admit(&mut members, Member(m.author().clone(), inviter.clone(), *m.timestamp(), Role::Member)),
+                MessageKind::Left => Membership::Leave(m.author().clone(), m.author().clone(), *m.timestamp()).apply(&mut members),
+                MessageKind::Removed(name) => Membership::Leave(name.clone(), m.author().clone(), *m.timestamp()).apply(&mut members),
+                _ => Membership::admit(&mut members, Member(m.author().clone(), None, *m.timestamp(), Role::Member)),
+            }
+        }
+        members
+    }
+
+    /// Whether `name` is an admin of the room.
+    pub fn is_admin(room: &Room, name: &OrangeName) -> bool {
+        Membership::is_admin(&room.1, name)
+    }
 }
 
-pub type Room = (Id, Vec<OrangeName>, Vec<Message>);
+pub type Room = (Id, Vec<Member>, Vec<Message>, ReadCursors, Vec<OrangeName>, RoomMetadata, RoomModeration);
 
 static ROOMS: LazyLock<Id> = LazyLock::new(|| Id::hash(&"RoomsV1".to_string()));
 static MESSAGES: LazyLock<Id> = LazyLock::new(|| Id::hash(&"MessagesV1".to_string()));
+static REPORTS: LazyLock<Id> = LazyLock::new(|| Id::hash(&"ReportsV1".to_string()));
 
+/// Members can read and write a room's records. Admins also hold its delete key, which is how they are told apart in AIR.
 const ROOMS_PERMISSIONS: Permissions = Permissions::new(Some((true, true)), None, BTreeMap::new());
+const ROOMS_ADMIN_PERMISSIONS: Permissions = Permissions::new(Some((true, true)), Some(true), BTreeMap::new());
 const MESSAGES_PERMISSIONS: Permissions = Permissions::new(None, None, BTreeMap::new());
 
 static ROOMS_PROTOCOL: LazyLock<Protocol> = LazyLock::new(|| {
@@ -66,98 +723,498 @@ static MESSAGES_PROTOCOL: LazyLock<Protocol> = LazyLock::new(|| {
     Protocol::new(validation, header, *MESSAGES)
 });
 
-#[derive(Serialize, Deserialize, Debug)]
+/// A report is a record of its own, shared with the room's admins and no one else.
+const REPORTS_PERMISSIONS: Permissions = Permissions::new(None, None, BTreeMap::new());
+
+static REPORTS_PROTOCOL: LazyLock<Protocol> = LazyLock::new(|| {
+    let validation = Validation::new(None, None, BTreeMap::new(), false);
+    let header = HeaderInfo::new(None, BTreeMap::new(), Vec::new());
+    Protocol::new(validation, header, *REPORTS)
+});
+
+/// Typing states and read cursors are records each member overwrites in place. Such a record carries its delete key,
+/// which AIR needs to overwrite it. Every member can read that key, but they could already claim anyone is typing or has read.
+/// It shares the messages' protocol id so rooms accept it as a child.
+const OVERWRITTEN_PERMISSIONS: Permissions = Permissions::new(None, Some(true), BTreeMap::new());
+
+static OVERWRITTEN_PROTOCOL: LazyLock<Protocol> = LazyLock::new(|| {
+    let validation = Validation::new(None, Some(true), BTreeMap::new(), false);
+    let header = HeaderInfo::new(Some(KeyGen::Derive(0)), BTreeMap::new(), Vec::new());
+    Protocol::new(validation, header, *MESSAGES)
+});
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum RoomsRequest {
     CreateRoom(Uuid),
+    /// Lists a new public room, found again by its `Uuid` once `PublicRoomsSync` reads it back.
+    Publish(Uuid, RoomMetadata),
     CreateMessage(Id, Message),
+    EditMessage(Id, Uuid, OrangeName, String),
+    DeleteMessage(Id, Uuid, OrangeName),
+    React(Id, Uuid, OrangeName, String),
+    Unreact(Id, Uuid, OrangeName, String),
     Share(Id, OrangeName),
+    /// Sets the name, topic and avatar of a group. A new name is also announced in the timeline.
+    UpdateMetadata(Id, OrangeName, RoomMetadata),
+    /// Adds me to a room I created or joined without an invite.
+    Join(Id, OrangeName),
+    /// AIR cannot take back keys it has already shared, so leaving and removal are membership
+    /// messages that every member's `RoomsSync` enforces.
+    Leave(Id, OrangeName),
+    Remove(Id, OrangeName, OrangeName),
+    /// Gives a member a new role. Promoted admins are shared the room's admin permissions.
+    SetRole(Id, OrangeName, OrangeName, Role),
+    /// Writes the full photo of a message sent just before it.
+    Upload(Id, Blob),
+    /// Writes the file attached to a message sent just before it by its message id, author, path and size.
+    /// Each chunk is read from the file as it goes out, so the outbox only holds where the file is.
+    UploadFile(Id, Uuid, OrangeName, PathBuf, u64),
+    /// Mutes and bans. A ban is also announced in the timeline as a removal.
+    Moderate(Id, Moderation),
+    /// Sends a report about a message in the room to the given admins, as a private record shared with each of them.
+    Report(Id, Report, Vec<OrangeName>),
+    /// Moves my read cursor by overwriting my one cursor record for the room.
+    Read(Id, OrangeName, DateTime<Utc>),
+    /// Sent straight away instead of through the `Outbox`, as a typing state that arrives late is wrong anyway.
+    Typing(Id, OrangeName),
+}
+
+impl RoomsRequest {
+    /// The room the request writes to. Requests for the same room reach AIR in the order they were made.
+    fn room(&self) -> Option<Id> {
+        match self {
+            RoomsRequest::CreateRoom(..) | RoomsRequest::Publish(..) => None,
+            RoomsRequest::CreateMessage(room, ..) | RoomsRequest::EditMessage(room, ..) | RoomsRequest::DeleteMessage(room, ..) |
+            RoomsRequest::React(room, ..) | RoomsRequest::Unreact(room, ..) | RoomsRequest::Share(room, ..) |
+            RoomsRequest::UpdateMetadata(room, ..) | RoomsRequest::Join(room, ..) | RoomsRequest::Leave(room, ..) |
+            RoomsRequest::Remove(room, ..) | RoomsRequest::SetRole(room, ..) | RoomsRequest::Upload(room, ..) | RoomsRequest::UploadFile(room, ..) |
+            RoomsRequest::Moderate(room, ..) | RoomsRequest::Report(room, ..) | RoomsRequest::Read(room, ..) | RoomsRequest::Typing(room, ..) => Some(*room),
+        }
+    }
+}
+
+/// Counts through the writes a request makes, so retrying it skips the ones that already reached AIR.
+/// The first field is how many have been made so far and is kept with the request in the `Outbox`.
+struct Progress<'a>(&'a mut u32, u32);
+
+impl Progress<'_> {
+    /// Moves on to the next write and tells whether it still has to be made.
+    fn pending(&mut self) -> bool {
+        self.1 += 1;
+        self.1 > *self.0
+    }
+
+    fn done(&mut self) {
+        *self.0 = self.1;
+    }
+
+    /// Moves past the next write if an earlier attempt already made it.
+    fn skip(&mut self) -> bool {
+        let made = self.1 < *self.0;
+        if made {self.1 += 1;}
+        made
+    }
 }
 
 #[derive(Debug)]
 pub struct RoomsService{
+    outbox: Outbox,
+}
+
+impl RoomsService {
+    /// Writes a record to the room, unless an earlier attempt at the request already did.
+    async fn write(ctx: &mut ThreadContext<(Id, Uuid, Delivery), RoomsRequest>, cache: &RoomsCache, room: Id, payload: Vec<u8>, progress: &mut Progress<'_>) -> Result<(), runtime::Error> {
+        if !progress.pending() {return Ok(());}
+        match cache.public.contains(&room) {
+            true => {AirService::create_public(ctx, PublicItem{protocol: public_stream(room), header: Vec::new(), payload}).await?;},
+            false => {Self::create(ctx, cache, room, &MESSAGES_PROTOCOL, MESSAGES_PERMISSIONS, payload).await?;},
+        }
+        progress.done();
+        Ok(())
+    }
+
+    /// Creates a record at the next free index of a private room.
+    async fn create(ctx: &mut ThreadContext<(Id, Uuid, Delivery), RoomsRequest>, cache: &RoomsCache, room: Id, protocol: &Protocol, perms: Permissions, payload: Vec<u8>) -> Result<RecordPath, runtime::Error> {
+        let path = RecordPath::root().join(room);
+        let mut x = cache.rooms.get(&path).ok_or(AirError::Validation(ValidationError::MissingRecord(path.to_string())))?.2;
+        loop {
+            match AirService::create_private(ctx, path.clone(), protocol.clone(), x, perms.clone(), payload.clone()).await? {
+                (_, Some(_)) => x += 1,
+                (record, None) => return Ok(record),
+            }
+        }
+    }
+
+    /// Writes my typing state or read cursor by overwriting my record of it for the room, creating it the first time.
+    /// In public rooms it is a public item on `stream` under an id derived from it and my name, which AIR lets only me replace.
+    /// In private rooms the path of the record is kept in the cache under `records`.
+    async fn overwrite(ctx: &mut ThreadContext<(Id, Uuid, Delivery), RoomsRequest>, cache: &RoomsCache, room: Id, name: OrangeName, event: RoomEvent, stream: Id, records: &str) -> Result<(), runtime::Error> {
+        let payload = serde_json::to_vec(&event)?;
+        if cache.public.contains(&room) {
+            let item = PublicItem{protocol: stream, header: Vec::new(), payload};
+            return match ctx.blocking_request::<AirService>(AirRequest::UpdatePublic(Id::hash(&(stream, name)), item)).await? {
+                AirResponse::Empty => Ok(()),
+                r => Err(AirError::MaliciousResponse(format!("{r:?}")).into()),
+            };
+        }
+        // Keyed by the room's path, as the cache is JSON and an `Id` can't be a map key there.
+        let mut paths: BTreeMap<RecordPath, RecordPath> = ctx.hardware.cache.get(records).await;
+        if let Some(path) = paths.get(&RecordPath::root().join(room)) {
+            let request = AirRequest::UpdatePrivate(path.clone(), OVERWRITTEN_PERMISSIONS, payload.clone());
+            if let Ok(AirResponse::UpdatePrivate(true)) = ctx.blocking_request::<AirService>(request).await {return Ok(());}
+        }
+        let path = Self::create(ctx, cache, room, &OVERWRITTEN_PROTOCOL, OVERWRITTEN_PERMISSIONS, payload).await?;
+        paths.insert(RecordPath::root().join(room), path);
+        ctx.hardware.cache.set(records, &paths).await;
+        Ok(())
+    }
+
+    /// Writes a membership change followed by the notice shown for it in the timeline.
+    async fn membership(ctx: &mut ThreadContext<(Id, Uuid, Delivery), RoomsRequest>, cache: &RoomsCache, room: Id, record: Membership, notice: Message, progress: &mut Progress<'_>) -> Result<(), runtime::Error> {
+        Self::write(ctx, cache, room, serde_json::to_vec(&record)?, progress).await?;
+        Self::write(ctx, cache, room, serde_json::to_vec(&notice)?, progress).await
+    }
+
+    /// Makes the writes of a request, skipping the `done` ones an earlier attempt already made.
+    async fn handle(ctx: &mut ThreadContext<(Id, Uuid, Delivery), RoomsRequest>, cache: &mut RoomsCache, request: RoomsRequest, done: &mut u32) -> Result<(), runtime::Error> {
+        let progress = &mut Progress(done, 0);
+        match request {
+            RoomsRequest::CreateRoom(uuid) => {
+                while let (_, Some(_)) = AirService::create_private(ctx, RecordPath::root(), ROOMS_PROTOCOL.clone(), cache.rooms_idx, ROOMS_ADMIN_PERMISSIONS, serde_json::to_vec(&uuid)?).await? {
+                    cache.rooms_idx += 1;
+                }
+            },
+            RoomsRequest::Publish(uuid, metadata) => {
+                let item = PublicItem{protocol: *PUBLIC_ROOMS, header: Vec::new(), payload: serde_json::to_vec(&(uuid, metadata))?};
+                AirService::create_public(ctx, item).await?;
+            },
+            RoomsRequest::CreateMessage(room, message) => {
+                Self::write(ctx, cache, room, serde_json::to_vec(&message)?, progress).await?;
+            },
+            RoomsRequest::EditMessage(room, key, name, message) => {
+                let event = RoomEvent::Edit(key, message, Utc::now(), name);
+                Self::write(ctx, cache, room, serde_json::to_vec(&event)?, progress).await?;
+            },
+            RoomsRequest::DeleteMessage(room, key, name) => {
+                let event = RoomEvent::Delete(key, Utc::now(), name);
+                Self::write(ctx, cache, room, serde_json::to_vec(&event)?, progress).await?;
+            },
+            RoomsRequest::React(room, key, name, emoji) => {
+                let event = RoomEvent::React(key, name, emoji);
+                Self::write(ctx, cache, room, serde_json::to_vec(&event)?, progress).await?;
+            },
+            RoomsRequest::Unreact(room, key, name, emoji) => {
+                let event = RoomEvent::Unreact(key, name, emoji);
+                Self::write(ctx, cache, room, serde_json::to_vec(&event)?, progress).await?;
+            },
+            RoomsRequest::Read(room, name, timestamp) => {
+                let event = RoomEvent::Read(name.clone(), timestamp);
+                Self::overwrite(ctx, cache, room, name, event, read_stream(room), "ReadRecords").await?;
+            },
+            RoomsRequest::Typing(room, name) => {
+                let event = RoomEvent::TypingState(name.clone(), Utc::now());
+                Self::overwrite(ctx, cache, room, name, event, typing_stream(room), "TypingRecords").await?;
+            },
+            RoomsRequest::Share(room, name) => {
+                let inviter: Option<OrangeName> = ctx.hardware.cache.get("OrangeName").await;
+                if !cache.public.contains(&room) && progress.pending() {
+                    AirService::share(ctx, name.clone(), ROOMS_PERMISSIONS, RecordPath::root().join(room)).await?;
+                    progress.done();
+                }
+                let notice = Message::system(name.clone(), MessageKind::Joined(inviter.clone()));
+                Self::membership(ctx, cache, room, Membership::Join(Member::new(name, inviter)), notice, progress).await?;
+            },
+            RoomsRequest::UpdateMetadata(room, name, metadata) => {
+                let path = RecordPath::root().join(room);
+                let renamed = metadata.name().filter(|n| cache.metadata.get(&path).and_then(RoomMetadata::name) != Some(*n)).cloned();
+                let event = RoomEvent::Metadata(name.clone(), metadata);
+                Self::write(ctx, cache, room, serde_json::to_vec(&event)?, progress).await?;
+                if let Some(title) = renamed {
+                    let notice = Message::system(name, MessageKind::Renamed(title));
+                    Self::write(ctx, cache, room, serde_json::to_vec(&notice)?, progress).await?;
+                }
+            },
+            RoomsRequest::Join(room, name) => {
+                let notice = Message::system(name.clone(), MessageKind::Joined(None));
+                Self::membership(ctx, cache, room, Membership::Join(Member::new(name, None)), notice, progress).await?;
+            },
+            RoomsRequest::Leave(room, name) => {
+                let notice = Message::system(name.clone(), MessageKind::Left);
+                Self::membership(ctx, cache, room, Membership::Leave(name.clone(), name, Utc::now()), notice, progress).await?;
+            },
+            RoomsRequest::Remove(room, by, name) => {
+                let notice = Message::system(by.clone(), MessageKind::Removed(name.clone()));
+                Self::membership(ctx, cache, room, Membership::Leave(name, by, Utc::now()), notice, progress).await?;
+            },
+            RoomsRequest::SetRole(room, by, name, role) => {
+                if role == Role::Admin && !cache.public.contains(&room) && progress.pending() {
+                    AirService::share(ctx, name.clone(), ROOMS_ADMIN_PERMISSIONS, RecordPath::root().join(room)).await?;
+                    progress.done();
+                }
+                let notice = Message::system(by.clone(), MessageKind::RoleChanged(name.clone(), role));
+                Self::membership(ctx, cache, room, Membership::Role(name, role, by, Utc::now()), notice, progress).await?;
+            },
+            RoomsRequest::Upload(room, blob) => {
+                Self::write(ctx, cache, room, serde_json::to_vec(&blob)?, progress).await?;
+            },
+            RoomsRequest::UploadFile(room, key, author, path, size) => {
+                let count = Blob::count(size);
+                for index in 0..count {
+                    if progress.skip() {continue;}
+                    // A file that can't be read anymore is given up on rather than holding back the room for good.
+                    let Some(data) = Blob::read(&path, index, size) else {break};
+                    Self::write(ctx, cache, room, serde_json::to_vec(&Blob::Chunk(key, author.clone(), index, count, data))?, progress).await?;
+                }
+            },
+            RoomsRequest::Report(room, report, admins) => {
+                // Retrying after some admins got the report creates it again for the rest.
+                let payload = serde_json::to_vec(&(room, report))?;
+                let mut x = cache.rooms_idx;
+                let path = loop {
+                    match AirService::create_private(ctx, RecordPath::root(), REPORTS_PROTOCOL.clone(), x, REPORTS_PERMISSIONS, payload.clone()).await? {
+                        (_, Some(_)) => x += 1,
+                        (path, None) => break path,
+                    }
+                };
+                for admin in admins {
+                    if !progress.pending() {continue;}
+                    AirService::share(ctx, admin, REPORTS_PERMISSIONS, path.clone()).await?;
+                    progress.done();
+                }
+            },
+            RoomsRequest::Moderate(room, record) => {
+                let removed = match &record {
+                    Moderation::Ban(name, by, _) => Some(Message::system(by.clone(), MessageKind::Removed(name.clone()))),
+                    _ => None,
+                };
+                Self::write(ctx, cache, room, serde_json::to_vec(&record)?, progress).await?;
+                if let Some(notice) = removed {
+                    Self::write(ctx, cache, room, serde_json::to_vec(&notice)?, progress).await?;
+                }
+            },
+        }
+        Ok(())
+    }
 }
 
 impl Services for RoomsService {
     fn services() -> ServiceList {
         let mut services = ServiceList::default();
         services.insert::<RoomsSync>();
+        services.insert::<PublicRoomsSync>();
         services
     }
 }
 
 #[async_trait]
 impl Service for RoomsService {
-    type Send = ();
+    type Send = (Id, Uuid, Delivery);
     type Receive = RoomsRequest;
 
-    async fn new(_hardware: &mut hardware::Context) -> Self {
+    async fn new(hardware: &mut hardware::Context) -> Self {
         RoomsService{
+            outbox: Outbox::from_cache(&mut hardware.cache).await,
         }
     }
 
     async fn run(&mut self, ctx: &mut ThreadContext<Self::Send, Self::Receive>) -> Result<Option<Duration>, runtime::Error> {
-        let mut cache = RoomsCache::from_cache(&mut ctx.hardware.cache).await;
+        let mut changed = false;
+        let mut typing = Vec::new();
         while let Some((_, request)) = ctx.get_request() {
             match request {
-                RoomsRequest::CreateRoom(uuid) => {
-                    while let (_, Some(_)) = AirService::create_private(ctx, RecordPath::root(), ROOMS_PROTOCOL.clone(), cache.rooms_idx, ROOMS_PERMISSIONS, serde_json::to_vec(&uuid)?).await? {
-                        cache.rooms_idx += 1;
-                    }
-                },
-                RoomsRequest::CreateMessage(room, message) => {
-                    let mut x = cache.rooms.get(&RecordPath::root().join(room)).unwrap().2;
-                    while let (_, Some(_)) = AirService::create_private(ctx, RecordPath::root().join(room), MESSAGES_PROTOCOL.clone(), x, MESSAGES_PERMISSIONS, serde_json::to_vec(&message)?).await? {
-                        x += 1;
-                    }
-                },
-                RoomsRequest::Share(room, name) => {
-                    let message = Message::invisible(name.clone());
-                    let path = RecordPath::root().join(room);
-                    AirService::share(ctx, name, ROOMS_PERMISSIONS, path).await?;
-                    let mut x = cache.rooms.get(&RecordPath::root().join(room)).unwrap().2;
-                    while let (_, Some(_)) = AirService::create_private(ctx, RecordPath::root().join(room), MESSAGES_PROTOCOL.clone(), x, MESSAGES_PERMISSIONS, serde_json::to_vec(&message)?).await? {
-                        x += 1;
+                RoomsRequest::Typing(..) => typing.push(request),
+                request => {
+                    self.outbox.push(request);
+                    changed = true;
+                }
+            }
+        }
+
+        if self.outbox.is_due() || !typing.is_empty() {
+            let mut cache = RoomsCache::from_cache(&mut ctx.hardware.cache).await;
+            cache.public = PublicRoomsCache::from_cache(&mut ctx.hardware.cache).await.rooms.iter().map(PublicRoom::id).collect();
+            for request in typing {
+                let _ = Self::handle(ctx, &mut cache, request, &mut 0).await;
+            }
+            let me: Option<OrangeName> = ctx.hardware.cache.get("OrangeName").await;
+            let mut blocked = BTreeSet::new();
+            let mut i = 0;
+            while let Some((request, attempts, at, done)) = self.outbox.0.get_mut(i) {
+                let room = request.room();
+                if room.is_some_and(|room| blocked.contains(&room)) || *at > Utc::now() {
+                    blocked.extend(room);
+                    i += 1;
+                    continue;
+                }
+                let sent = match &*request {
+                    RoomsRequest::CreateMessage(room, message) => Some((*room, message.id())),
+                    _ => None
+                };
+                // The error says whether the request can never succeed, in which case it is dropped rather than retried.
+                let result = match me.as_ref().is_some_and(|me| cache.has_left(request, me)) {
+                    true => Err(true),
+                    false => Self::handle(ctx, &mut cache, request.clone(), done).await.map_err(|error| Outbox::is_permanent(&error)),
+                };
+                if let Some((room, key)) = sent {
+                    ctx.callback((room, key, if result.is_ok() {Delivery::Sent} else {Delivery::Failed}));
+                }
+                match result {
+                    Ok(()) | Err(true) => {self.outbox.0.remove(i);},
+                    Err(false) => {
+                        *attempts += 1;
+                        *at = Outbox::backoff(*attempts);
+                        blocked.extend(room);
+                        i += 1;
                     }
-                },
+                }
+                changed = true;
             }
         }
 
+        if changed {self.outbox.cache(&mut ctx.hardware.cache).await;}
         Ok(Some(Duration::from_millis(16)))
     }
 
-    fn callback(_state: &mut State, _response: Self::Send) {
-        // let mut rooms = state.get::<Rooms>().0;
-        // // if response.2 {state.set(&Name(Some(response.0.clone())));}
-        // rooms.insert(response.0, response.1);
-        // state.set(&Rooms(rooms));
+    fn callback(state: &mut State, (room, key, delivery): Self::Send) {
+        state.get_mut_or_default::<Rooms>().set_delivery(room, key, delivery);
+    }
+}
+
+/// Requests that have not reached AIR yet, kept in the cache so they survive restarts.
+/// Each entry holds the number of failed attempts, when to try it next and how many of its writes were made.
+/// Requests for a room go out in order: one that fails holds back the later ones for the same room.
+/// Requests for no room in particular never hold each other back, and ones that can never succeed are dropped.
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct Outbox(Vec<(RoomsRequest, u32, DateTime<Utc>, u32)>);
+
+impl Outbox {
+    const MAX_BACKOFF: i64 = 300;
+
+    /// Queues a request to be sent now. Sending a message again replaces its queued copy,
+    /// and a newer read cursor replaces one for the same room that hasn't gone out yet.
+    fn push(&mut self, request: RoomsRequest) {
+        match &request {
+            RoomsRequest::CreateMessage(_, message) => {
+                let key = message.id();
+                self.0.retain(|(r, _, _, _)| !matches!(r, RoomsRequest::CreateMessage(_, m) if m.id() == key));
+            },
+            RoomsRequest::Read(room, ..) => self.0.retain(|(r, _, _, _)| !matches!(r, RoomsRequest::Read(id, ..) if id == room)),
+            _ => {}
+        }
+        self.0.push((request, 0, Utc::now(), 0));
+    }
+
+    fn is_due(&self) -> bool {
+        self.0.iter().any(|(_, _, at, _)| *at <= Utc::now())
+    }
+
+    /// When to try a request again after it failed `attempts` times.
+    fn backoff(attempts: u32) -> DateTime<Utc> {
+        let backoff = 2i64.pow(attempts.min(9)).min(Self::MAX_BACKOFF);
+        Utc::now() + chrono::Duration::seconds(backoff)
+    }
+
+    /// Whether a request failed because AIR refused it, as it does a write to a room I can no longer write to,
+    /// or because it can't be encoded, rather than because AIR couldn't be reached. Retrying won't change either.
+    /// The runtime hands errors over as text, which names the kind of `AirError` first.
+    fn is_permanent(error: &runtime::Error) -> bool {
+        let error = error.to_string();
+        ["Validation(", "SerdeJson("].iter().any(|kind| error.starts_with(kind))
+    }
+
+    /// The messages still waiting to go out, so the echoes shown for them can be restored after a restart.
+    /// Ones that have failed before show as failed, like they did before the restart.
+    async fn unsent(cache: &mut Cache) -> Vec<(Id, Message, Delivery)> {
+        Self::from_cache(cache).await.0.into_iter().filter_map(|(request, attempts, ..)| match request {
+            RoomsRequest::CreateMessage(room, message) => Some((room, message, if attempts == 0 {Delivery::Pending} else {Delivery::Failed})),
+            _ => None,
+        }).collect()
+    }
+
+    async fn cache(&self, cache: &mut Cache) {
+        cache.set("RoomsOutbox", self).await;
+    }
+
+    async fn from_cache(cache: &mut Cache) -> Self {
+        cache.get("RoomsOutbox").await
     }
 }
 
 #[derive(Debug)]
 pub struct RoomsSync{
     cache: RoomsCache,
+    typing: BTreeMap<RecordPath, BTreeMap<OrangeName, DateTime<Utc>>>,
+    /// Attachments a page asked for that haven't fully arrived yet.
+    wanted: BTreeSet<Uuid>,
     init: bool 
 }
 
+impl RoomsSync {
+    /// How long a typing record stays visible without a newer one.
+    const TYPING_TIMEOUT: chrono::Duration = chrono::Duration::seconds(5);
+
+    /// Whether `name` is an admin of the room so far, by its members record or, for older rooms, its messages.
+    fn is_admin(members: &BTreeMap<RecordPath, Vec<Member>>, room: &RecordPath, messages: &[Message], name: &OrangeName) -> bool {
+        match members.get(room) {
+            Some(members) => Membership::is_admin(members, name),
+            None => Membership::is_admin(&Rooms::legacy_members(messages), name),
+        }
+    }
+
+    /// Takes the wanted attachments that have fully arrived, from this sync or `PublicRoomsSync`.
+    async fn load_wanted(&mut self, cache: &mut Cache) -> Vec<(Uuid, String)> {
+        let mut attachments = Vec::new();
+        for key in self.wanted.clone() {
+            if let Some(data) = Attachments::load(cache, key).await {
+                self.wanted.remove(&key);
+                attachments.push((key, data));
+            }
+        }
+        attachments
+    }
+
+    /// Whether a typing state written at `timestamp` still holds: it is recent and they haven't sent a message since.
+    fn is_typing(messages: &[Message], name: &OrangeName, timestamp: DateTime<Utc>) -> bool {
+        Utc::now() - timestamp < Self::TYPING_TIMEOUT && !messages.iter().rev().take_while(|m| *m.timestamp() >= timestamp).any(|m| m.author() == name)
+    }
+}
+
 impl Services for RoomsSync {}
 
 #[async_trait]
 impl Service for RoomsSync {
-    type Send = Vec<(Uuid, Room)>;
-    type Receive = ();
+    /// The rooms and the reports shared with me, when they changed, and any attachments asked for that are now stored.
+    /// The first rooms sent come with the messages still in the outbox, to show again.
+    type Send = (Option<(Vec<(Uuid, Room)>, Reports, Vec<(Id, Message, Delivery)>)>, Vec<(Uuid, String)>);
+    /// An attachment a page wants to show, sent by `MessagesPlugin::load_attachment`.
+    type Receive = Uuid;
 
     async fn new(hardware: &mut hardware::Context) -> Self {
         RoomsSync{
             cache: RoomsCache::from_cache(&mut hardware.cache).await,
+            typing: BTreeMap::new(),
+            wanted: BTreeSet::new(),
             init: false
         }
     }
 
     async fn run(&mut self, ctx: &mut ThreadContext<Self::Send, Self::Receive>) -> Result<Option<Duration>, runtime::Error> {
         let mut mutated = false;
+        while let Some((_, key)) = ctx.get_request() {
+            self.wanted.insert(key);
+        }
+        let attachments = self.load_wanted(&mut ctx.hardware.cache).await;
+        if !attachments.is_empty() {ctx.callback((None, attachments));}
         println!("running {:?}", self.cache.rooms_idx);
 
-        for (_, path) in AirService::receive(ctx, self.cache.datetime).await?.into_iter() {
+        for (sender, path) in AirService::receive(ctx, self.cache.datetime).await?.into_iter() {
+            if let Ok(Some((record, _))) = AirService::read_private(ctx, path.clone()).await
+                && let Ok((room, report)) = serde_json::from_slice::<(Id, Report)>(&record.payload) {
+                if report.1 == sender {self.cache.reports.insert(room, report);}
+                mutated = true;
+                continue;
+            }
+
             // let uuid: Uuid = serde_json::from_slice(&AirService::read_private(ctx, path.clone()).await?.unwrap().0.payload).unwrap();
             // self.cache.rooms.entry(path).or_insert((uuid, vec![], 0));
             // mutated = true;
@@ -180,7 +1237,7 @@ impl Service for RoomsSync {
             if let Some(path) = path {
                 if let Ok(uuid) = serde_json::from_slice(&AirService::read_private(ctx, path.clone()).await?.unwrap().0.payload) {
                     println!("Uuid: {:?}...", uuid);
-                    self.cache.rooms.entry(path).or_insert((uuid, vec![], 0));
+                    self.cache.rooms.entry(path).or_insert((uuid, vec![], 0, BTreeMap::new()));
                     mutated = true;
                 } else {println!("_--- ROOM HAD NO UUID ---_");}
             }
@@ -188,27 +1245,100 @@ impl Service for RoomsSync {
         }
         println!("Done discovering.");
 
-        for (room, (_, messages, index)) in &mut self.cache.rooms {
+        let mut blobs = Vec::new();
+        for (room, (_, messages, index, cursors)) in &mut self.cache.rooms {
+            let typing = self.typing.entry(room.clone()).or_default();
+            let members = &mut self.cache.members;
+            let metadata = &mut self.cache.metadata;
+            let moderation = &mut self.cache.moderation;
+            let typists = &mut self.cache.typists;
+            let readers = &mut self.cache.readers;
             while let (path, Some(_)) = AirService::discover(ctx, room.clone(), *index, vec![MESSAGES_PROTOCOL.clone()]).await? {
                 if let Some(path) = path {
-                    if let Ok(message) = serde_json::from_slice(&AirService::read_private(ctx, path).await?.unwrap().0.payload) {
-                        messages.insert(*index as usize, message);
-                        mutated = true;
+                    match serde_json::from_slice(&AirService::read_private(ctx, path.clone()).await?.unwrap().0.payload) {
+                        Ok(record) if record.author().is_some_and(|author| moderation.get(room).is_some_and(|m| m.drops(author, &record))) => {},
+                        Ok(RoomRecord::Message(message)) if !message.is_system() && members.get(room).is_some_and(|m| !m.iter().any(|m| m.name() == message.author())) => {},
+                        Ok(RoomRecord::Message(message)) if matches!(message.kind(), MessageKind::Renamed(_)) && !Self::is_admin(members, room, messages, message.author()) => {},
+                        Ok(RoomRecord::Message(mut message)) => {
+                            message.upgrade();
+                            typing.remove(message.author());
+                            messages.push(message);
+                        },
+                        Ok(RoomRecord::Event(RoomEvent::TypingState(..))) => {
+                            typists.entry(room.clone()).or_default().insert(path);
+                        },
+                        Ok(RoomRecord::Event(RoomEvent::Read(name, timestamp))) => {
+                            readers.entry(room.clone()).or_default().insert(name.clone(), path);
+                            RoomEvent::Read(name, timestamp).apply(messages, cursors);
+                        },
+                        Ok(RoomRecord::Event(RoomEvent::Metadata(author, update))) => {
+                            if Self::is_admin(members, room, messages, &author) {metadata.insert(room.clone(), update);}
+                        },
+                        Ok(RoomRecord::Event(event)) => event.apply(messages, cursors),
+                        Ok(RoomRecord::Membership(record)) => {
+                            record.apply(members.entry(room.clone()).or_insert_with(|| Rooms::legacy_members(messages)));
+                        },
+                        Ok(RoomRecord::Moderation(record)) => {
+                            let members = members.entry(room.clone()).or_insert_with(|| Rooms::legacy_members(messages));
+                            record.apply(moderation.entry(room.clone()).or_default(), members, messages);
+                        },
+                        Ok(RoomRecord::Blob(blob)) => {
+                            if messages.iter().any(|m| blob.fits(m)) {blobs.push(blob);}
+                        },
+                        Err(_) => {}
                     }
+                    mutated = true;
                 }
                 *index += 1;
             }
+
+            for (name, path) in readers.get(room).into_iter().flatten() {
+                let Some((record, _)) = AirService::read_private(ctx, path.clone()).await? else {continue};
+                if let Ok(RoomEvent::Read(reader, timestamp)) = serde_json::from_slice(&record.payload) && reader == *name {
+                    let before = cursors.get(name).copied();
+                    RoomEvent::Read(reader, timestamp).apply(messages, cursors);
+                    mutated |= cursors.get(name).copied() != before;
+                }
+            }
+
+            let before = typing.keys().cloned().collect::<Vec<_>>();
+            for path in typists.get(room).into_iter().flatten() {
+                let Some((record, _)) = AirService::read_private(ctx, path.clone()).await? else {continue};
+                if let Ok(RoomEvent::TypingState(name, timestamp)) = serde_json::from_slice(&record.payload)
+                    && Self::is_typing(messages, &name, timestamp)
+                    && !moderation.get(room).is_some_and(|m| m.is_banned(&name)) {
+                    typing.insert(name, Utc::now());
+                }
+            }
+
+            typing.retain(|_, seen| Utc::now() - *seen < Self::TYPING_TIMEOUT);
+            mutated |= !typing.keys().eq(before.iter());
         }
 
         println!("Done messages.");
+
+        Attachments::sync(&mut ctx.hardware.cache, &mut self.cache.attachments, &mut self.cache.chunks, blobs).await;
+        let attachments = self.load_wanted(&mut ctx.hardware.cache).await;
         
         if mutated || !self.init {
+            let unsent = match self.init {
+                true => Vec::new(),
+                false => Outbox::unsent(&mut ctx.hardware.cache).await,
+            };
             self.init = true;
-            ctx.callback(self.cache.rooms.iter().map(|(p, (u, m, _))| {
-                let authors: Vec<_> = m.iter().map(|Message(_, _, a, _)| a.clone()).collect::<HashSet<_>>().into_iter().collect();
-                (*u, (p.last(), authors, m.clone()))
-            }).collect());
+            let me: Option<OrangeName> = ctx.hardware.cache.get("OrangeName").await;
+            let rooms = self.cache.rooms.iter().filter_map(|(p, (u, m, _, c))| {
+                let members = self.cache.members.get(p).cloned().unwrap_or_else(|| Rooms::legacy_members(m));
+                if !members.is_empty() && me.as_ref().is_some_and(|me| !members.iter().any(|m| m.name() == me)) {return None;}
+                let typing = self.typing.get(p).map(|t| t.keys().cloned().collect()).unwrap_or_default();
+                let metadata = self.cache.metadata.get(p).cloned().unwrap_or_default();
+                let moderation = self.cache.moderation.get(p).cloned().unwrap_or_default();
+                Some((*u, (p.last(), members, m.clone(), c.clone(), typing, metadata, moderation)))
+            }).collect();
+            ctx.callback((Some((rooms, self.cache.reports.clone(), unsent)), attachments));
             println!("Callback done.");
+        } else if !attachments.is_empty() {
+            ctx.callback((None, attachments));
         }
 
         println!("Done updating.");
@@ -217,27 +1347,75 @@ impl Service for RoomsSync {
         Ok(Some(Duration::from_secs(1)))
     }
 
-    fn callback(state: &mut State, response: Self::Send) {
+    fn callback(state: &mut State, (response, attachments): Self::Send) {
         println!("Callback...");
-        state.set(Rooms(response))
+        let stored = state.get_mut_or_default::<Attachments>();
+        attachments.into_iter().for_each(|(key, data)| stored.insert(key, data));
+        let Some((response, reports, unsent)) = response else {return};
+        state.set(reports);
+        let mut rooms = Rooms(response);
+        let previous = state.get_or_default::<Rooms>().clone();
+        rooms.keep_unsent(&previous);
+        rooms.keep_public(&previous, state.get_or_default::<PublicRooms>());
+        rooms.restore(unsent);
+        state.set(rooms)
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct RoomsCache {
     pub rooms_idx: u32,
-    pub rooms: BTreeMap<RecordPath, (Uuid, Vec<Message>, u32)>,
+    pub rooms: BTreeMap<RecordPath, (Uuid, Vec<Message>, u32, ReadCursors)>,
     pub datetime: DateTime<Utc>,
+    /// Rooms without an entry predate `Membership` records and take their members from their messages.
+    #[serde(default)]
+    pub members: BTreeMap<RecordPath, Vec<Member>>,
+    #[serde(default)]
+    pub metadata: BTreeMap<RecordPath, RoomMetadata>,
+    #[serde(default)]
+    pub moderation: BTreeMap<RecordPath, RoomModeration>,
+    /// The messages whose full attachment is in the cache.
+    #[serde(default)]
+    pub attachments: BTreeSet<Uuid>,
+    /// The chunks of files that haven't fully arrived yet.
+    #[serde(default)]
+    pub chunks: BTreeMap<Uuid, BTreeSet<u32>>,
+    /// The typing record of each member in each room, read again on every sync as they are overwritten in place.
+    #[serde(default)]
+    pub typists: BTreeMap<RecordPath, BTreeSet<RecordPath>>,
+    /// The read cursor record of each member in each room, read again on every sync like the typing records.
+    /// Only the newest one found for a member is kept, as earlier versions wrote a record for every read.
+    #[serde(default)]
+    pub readers: BTreeMap<RecordPath, BTreeMap<OrangeName, RecordPath>>,
+    /// Reports shared with me, which the rooms they are about check when they are shown.
+    #[serde(default)]
+    pub reports: Reports,
+    /// Public rooms, whose records `RoomsService` publishes instead of writing them privately.
+    #[serde(skip)]
+    pub public: BTreeSet<Id>,
 }
 
 impl RoomsCache {
+    /// Whether I have left or been removed from the private room a request writes to, so it can never be delivered.
+    /// Rooms count me out the same way `RoomsSync` hides them, and joining is the one request made before I'm a member.
+    fn has_left(&self, request: &RoomsRequest, me: &OrangeName) -> bool {
+        let Some(room) = request.room() else {return false};
+        if self.public.contains(&room) || matches!(request, RoomsRequest::Join(..)) {return false;}
+        let path = RecordPath::root().join(room);
+        let Some((_, messages, ..)) = self.rooms.get(&path) else {return false};
+        let members = self.members.get(&path).cloned().unwrap_or_else(|| Rooms::legacy_members(messages));
+        !members.is_empty() && !members.iter().any(|m| m.name() == me)
+    }
+
     pub async fn cache(&self, cache: &mut Cache) {
         // let other = cache.get::<Cache>("RoomCache").await;
         cache.set("RoomCache", self).await;
     }
 
     pub async fn from_cache(cache: &mut Cache) -> Self {
-        cache.get("RoomCache").await
+        let mut rooms: Self = cache.get("RoomCache").await;
+        rooms.rooms.values_mut().flat_map(|(_, m, _, _)| m.iter_mut()).for_each(Message::upgrade);
+        rooms
     }
 
     // pub fn merge(self, other: Self) -> Self {
@@ -255,58 +1433,496 @@ impl Default for RoomsCache {
             rooms_idx: 0,
             rooms: BTreeMap::new(),
             datetime: DateTime::UNIX_EPOCH,
+            members: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            moderation: BTreeMap::new(),
+            attachments: BTreeSet::new(),
+            chunks: BTreeMap::new(),
+            typists: BTreeMap::new(),
+            readers: BTreeMap::new(),
+            reports: Reports::default(),
+            public: BTreeSet::new(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct PublicRoom(String, String, AvatarContent, Vec<OrangeName>, Vec<Message>); // title, subtitle, members, messages
+/// A room anyone can find and join, listed as a public item under its own protocol.
+/// Its records are public items too, under a protocol derived from its id, and AIR vouches for who wrote each one.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PublicRoom(Id, Uuid, RoomMetadata, Vec<Member>, Vec<Message>, ReadCursors, DateTime<Utc>, #[serde(default)] RoomModeration, #[serde(skip)] Vec<OrangeName>);
 impl PublicRoom {
-    pub fn new(t: &str, s: &str, p: Option<resources::Image>) -> Self {
-        let avatar = AvatarContentMessages::room(p);
-        PublicRoom(t.to_string(), s.to_string(), avatar, Vec::new(), Vec::new())
+    /// The member who published the room is its first admin.
+    fn new(id: Id, uuid: Uuid, metadata: RoomMetadata, author: OrangeName, published: DateTime<Utc>) -> Self {
+        let creator = Member(author, None, published, Role::Admin);
+        PublicRoom(id, uuid, metadata, vec![creator], Vec::new(), ReadCursors::new(), published, RoomModeration::default(), Vec::new())
     }
 
-    pub fn title(&mut self) -> &mut String {&mut self.0}
-    pub fn subtitle(&mut self) -> &mut String {&mut self.1}
-    pub fn avatar(&mut self) -> &mut AvatarContent {&mut self.2}
-    pub fn members(&self) -> &Vec<OrangeName> {&self.3}
+    pub fn id(&self) -> Id {self.0}
+    pub fn uuid(&self) -> Uuid {self.1}
+    pub fn metadata(&self) -> &RoomMetadata {&self.2}
+    pub fn title(&self) -> String {self.2.name().cloned().unwrap_or("Untitled room".to_string())}
+    pub fn description(&self) -> String {self.2.topic().cloned().unwrap_or_default()}
+    /// The room's avatar, or the default one when it has none or it can't be decoded.
+    pub fn avatar(&self, ctx: &mut Context) -> AvatarContent {
+        AvatarContentMessages::room(self.2.avatar().and_then(|avatar| EncodedImageMessages::decode(ctx, avatar)))
+    }
+    pub fn members(&self) -> &Vec<Member> {&self.3}
     pub fn messages(&self) -> &Vec<Message> {&self.4}
+    pub fn is_member(&self, name: &OrangeName) -> bool {self.3.iter().any(|m| m.name() == name)}
+    pub fn moderation(&self) -> &RoomModeration {&self.7}
+
+    pub fn room(&self) -> Room {
+        (self.0, self.3.clone(), self.4.clone(), self.5.clone(), self.8.clone(), self.2.clone(), self.7.clone())
+    }
+
+    /// Applies a record read from the room's stream, dropping any its AIR author couldn't have written
+    /// and any the room's moderation silences. Admins may delete anyone's messages.
+    /// Blobs are handed back in `blobs`, as they are kept outside the room.
+    fn apply(&mut self, author: &OrangeName, record: RoomRecord, blobs: &mut Vec<Blob>) {
+        match record {
+            _ if self.7.drops(author, &record) => {},
+            RoomRecord::Message(mut message) if message.author() == author && (message.is_system() || self.3.iter().any(|m| m.name() == author)) => {
+                message.upgrade();
+                self.4.push(message);
+            },
+            RoomRecord::Event(RoomEvent::Metadata(name, update)) if name == *author && Membership::is_admin(&self.3, author) => {
+                self.2 = update;
+            },
+            RoomRecord::Event(RoomEvent::React(_, ref name, _) | RoomEvent::Unreact(_, ref name, _) |
+                RoomEvent::Edit(.., ref name) | RoomEvent::Delete(.., ref name)) if name != author => {},
+            RoomRecord::Event(RoomEvent::Delete(key, ..)) if Membership::is_admin(&self.3, author) => {
+                if let Some(m) = self.4.iter_mut().find(|m| m.id() == key) {m.delete();}
+            },
+            RoomRecord::Event(RoomEvent::TypingState(..) | RoomEvent::Read(..)) => {},
+            RoomRecord::Event(event @ (RoomEvent::Edit(..) | RoomEvent::Delete(..) | RoomEvent::React(..) | RoomEvent::Unreact(..))) => event.apply(&mut self.4, &mut self.5),
+            RoomRecord::Membership(Membership::Join(ref member)) if member.1.is_none() && self.2.access() == Access::InviteOnly => {},
+            RoomRecord::Membership(record) if record.by() == author => record.apply(&mut self.3),
+            RoomRecord::Moderation(record) if record.by() == author => record.apply(&mut self.7, &mut self.3, &mut self.4),
+            RoomRecord::Blob(blob) if blob.author() == author && self.4.iter().any(|m| blob.fits(m)) => blobs.push(blob),
+            _ => {}
+        }
+    }
 }
 
+/// Every public room found so far, newest first.
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct PublicRooms(Vec<PublicRoom>);
 
 impl PublicRooms {
     pub fn inner(self) -> Vec<PublicRoom> {
         self.0
     }
+
+    pub fn get(&self, id: Id) -> Option<&PublicRoom> {
+        self.0.iter().find(|room| room.0 == id)
+    }
+}
+
+static PUBLIC_ROOMS: LazyLock<Id> = LazyLock::new(|| Id::hash(&"PublicRoomsV1".to_string()));
+
+/// The protocol a public room's records are published under.
+fn public_stream(room: Id) -> Id {
+    Id::hash(&(*PUBLIC_ROOMS, room))
+}
+
+/// The protocol of the typing states in a public room, one public item per member that they overwrite.
+fn typing_stream(room: Id) -> Id {
+    Id::hash(&(*PUBLIC_ROOMS, room, "Typing".to_string()))
+}
+
+/// The protocol of the read cursors in a public room, one public item per member that they overwrite.
+fn read_stream(room: Id) -> Id {
+    Id::hash(&(*PUBLIC_ROOMS, room, "Read".to_string()))
+}
+
+/// Syncs the directory of public rooms, and in full the ones I'm in.
+/// Rooms I'm not in only have what the directory shows read: their details, members and moderation.
+#[derive(Debug)]
+pub struct PublicRoomsSync{
+    cache: PublicRoomsCache,
+    /// Rooms I asked to join, read in full before my membership has synced.
+    joining: BTreeSet<Id>,
+    init: bool,
+}
+
+impl Services for PublicRoomsSync {}
+
+#[async_trait]
+impl Service for PublicRoomsSync {
+    /// The public rooms and my name, with the messages still in the outbox the first time, like `RoomsSync`.
+    type Send = (Vec<PublicRoom>, Option<OrangeName>, Vec<(Id, Message, Delivery)>);
+    /// A room I'm joining, sent by `MessagesPlugin::join_room`.
+    type Receive = Id;
+
+    async fn new(hardware: &mut hardware::Context) -> Self {
+        PublicRoomsSync{
+            cache: PublicRoomsCache::from_cache(&mut hardware.cache).await,
+            joining: BTreeSet::new(),
+            init: false,
+        }
+    }
+
+    async fn run(&mut self, ctx: &mut ThreadContext<Self::Send, Self::Receive>) -> Result<Option<Duration>, runtime::Error> {
+        let mut mutated = false;
+        while let Some((_, id)) = ctx.get_request() {
+            self.joining.insert(id);
+        }
+
+        let filter = Filter::new(None, None, Some(*PUBLIC_ROOMS), Some((Op::GR, self.cache.listed)));
+        for (id, author, item, published) in AirService::read_public(ctx, filter).await? {
+            self.cache.listed = self.cache.listed.max(published);
+            let Ok((uuid, metadata)) = serde_json::from_slice::<(Uuid, RoomMetadata)>(&item.payload) else {continue};
+            if self.cache.rooms.iter().any(|room| room.0 == id) {continue;}
+            self.cache.rooms.push(PublicRoom::new(id, uuid, metadata, author, published));
+            mutated = true;
+        }
+
+        let me: Option<OrangeName> = ctx.hardware.cache.get("OrangeName").await;
+        let mut blobs = Vec::new();
+        let directory = &mut self.cache.directory;
+        for room in &mut self.cache.rooms {
+            if !self.joining.contains(&room.0) && !me.as_ref().is_some_and(|me| room.is_member(me)) {
+                let read = directory.iter().find(|(id, _)| *id == room.0).map_or(room.6, |(_, read)| (*read).max(room.6));
+                let filter = Filter::new(None, None, Some(public_stream(room.0)), Some((Op::GR, read)));
+                let mut records = AirService::read_public(ctx, filter).await?;
+                records.sort_by_key(|(_, _, _, datetime)| *datetime);
+                for (_, author, item, datetime) in records {
+                    match directory.iter_mut().find(|(id, _)| *id == room.0) {
+                        Some((_, read)) => *read = (*read).max(datetime),
+                        None => directory.push((room.0, datetime)),
+                    }
+                    if let Ok(record @ (RoomRecord::Membership(_) | RoomRecord::Moderation(_) | RoomRecord::Event(RoomEvent::Metadata(..)))) = serde_json::from_slice(&item.payload) {
+                        room.apply(&author, record, &mut blobs);
+                        mutated = true;
+                    }
+                }
+                continue;
+            }
+            // Picks up from the last full read, applying again what the directory read since, which changes nothing.
+            directory.retain(|(id, _)| *id != room.0);
+
+            let filter = Filter::new(None, None, Some(public_stream(room.0)), Some((Op::GR, room.6)));
+            let mut records = AirService::read_public(ctx, filter).await?;
+            records.sort_by_key(|(_, _, _, datetime)| *datetime);
+            for (_, author, item, datetime) in records {
+                room.6 = room.6.max(datetime);
+                if let Ok(record) = serde_json::from_slice::<RoomRecord>(&item.payload) {room.apply(&author, record, &mut blobs);}
+                mutated = true;
+            }
+
+            let filter = Filter::new(None, None, Some(typing_stream(room.0)), Some((Op::GR, Utc::now() - RoomsSync::TYPING_TIMEOUT)));
+            let typing = AirService::read_public(ctx, filter).await?.into_iter().filter_map(|(_, author, item, _)| match serde_json::from_slice(&item.payload) {
+                Ok(RoomEvent::TypingState(name, timestamp)) if name == author && room.is_member(&author) && !room.7.is_banned(&author) && !room.7.is_muted(&author)
+                    && RoomsSync::is_typing(&room.4, &author, timestamp) => Some(author),
+                _ => None,
+            }).collect::<Vec<_>>();
+            if typing != room.8 {
+                room.8 = typing;
+                mutated = true;
+            }
+
+            let filter = Filter::new(None, None, Some(read_stream(room.0)), None);
+            for (_, author, item, _) in AirService::read_public(ctx, filter).await? {
+                if let Ok(RoomEvent::Read(name, timestamp)) = serde_json::from_slice(&item.payload) && name == author && room.is_member(&author) {
+                    let before = room.5.get(&author).copied();
+                    RoomEvent::Read(name, timestamp).apply(&mut room.4, &mut room.5);
+                    mutated |= room.5.get(&author).copied() != before;
+                }
+            }
+        }
+
+        let rooms = &self.cache.rooms;
+        self.joining.retain(|id| !rooms.iter().any(|room| room.0 == *id && me.as_ref().is_some_and(|me| room.is_member(me))));
+
+        Attachments::sync(&mut ctx.hardware.cache, &mut self.cache.attachments, &mut self.cache.chunks, blobs).await;
+
+        if mutated || !self.init {
+            let unsent = match self.init {
+                true => Vec::new(),
+                false => Outbox::unsent(&mut ctx.hardware.cache).await,
+            };
+            self.init = true;
+            let mut rooms = self.cache.rooms.clone();
+            rooms.sort_by_key(|room| std::cmp::Reverse(room.6));
+            ctx.callback((rooms, me, unsent));
+            self.cache.cache(&mut ctx.hardware.cache).await;
+        }
+        Ok(Some(Duration::from_secs(5)))
+    }
+
+    /// Also brings the public rooms I joined up to date in my rooms, keeping messages I haven't sent yet.
+    /// Rooms I joined but that haven't synced my membership yet are left as they are, unless I've been banned from them.
+    fn callback(state: &mut State, (response, me, unsent): Self::Send) {
+        let mut joined = Rooms(response.iter().filter(|r| me.as_ref().is_some_and(|me| r.is_member(me))).map(|r| (r.uuid(), r.room())).collect());
+        let banned = response.iter().filter(|r| me.as_ref().is_some_and(|me| r.moderation().is_banned(me))).map(PublicRoom::id).collect::<Vec<_>>();
+        let rooms = state.get_mut_or_default::<Rooms>();
+        joined.keep_unsent(rooms);
+        rooms.0.retain(|(uuid, room)| !joined.0.iter().any(|(u, _)| u == uuid) && !banned.contains(&room.0));
+        rooms.0.extend(joined.0);
+        rooms.restore(unsent);
+        state.set(PublicRooms(response))
+    }
+}
+
+/// What `PublicRoomsSync` has read so far, so only newer public items are fetched.
+#[derive(Debug, Serialize, Deserialize)]
+struct PublicRoomsCache {
+    listed: DateTime<Utc>,
+    rooms: Vec<PublicRoom>,
+    #[serde(default)]
+    attachments: BTreeSet<Uuid>,
+    #[serde(default)]
+    chunks: BTreeMap<Uuid, BTreeSet<u32>>,
+    /// How far the directory has read each room I'm not in, past the room's own last full read.
+    #[serde(default)]
+    directory: Vec<(Id, DateTime<Utc>)>,
+}
+
+impl PublicRoomsCache {
+    async fn cache(&self, cache: &mut Cache) {
+        cache.set("PublicRoomsCache", self).await;
+    }
+
+    async fn from_cache(cache: &mut Cache) -> Self {
+        cache.get("PublicRoomsCache").await
+    }
 }
 
-impl Default for PublicRooms {
+impl Default for PublicRoomsCache {
     fn default() -> Self {
-        let rooms = vec![
-            PublicRoom::new("the orange room", "A bright and friendly space for anything orange — the color, the fruit, or just the vibe.", None),
-            PublicRoom::new("space talk", "From Mars missions to stargazing tips, this room is for anyone curious about the cosmos.", None),
-            PublicRoom::new("animal studies", "Whether you work with animals or just admire them, this is a place to share facts, photos, and stories.", None),
-            PublicRoom::new("bike shed", "Pull up, park your ride, and swap stories with other motorbike and cycling enthusiasts.", None),
-            PublicRoom::new("coffee break", "Take five and join others for relaxed conversation over a virtual cup of coffee.", None),
-            PublicRoom::new("kyoto alley", "A calm corner for those who love Japanese culture, from side streets to seasonal snacks.", None),
-            PublicRoom::new("the gaming desk", "Talk strategies, share screenshots, or just hang out while you queue your next match.", None),
-            PublicRoom::new("football chat", "Join fans around the world to talk match day, player stats, or classic moments.", None),
-            PublicRoom::new("art studio", "Share your work, get feedback, or just hang around people who love to make things.", None),
-            PublicRoom::new("book circle", "Whether you're deep into fiction or just picking up something new, this room's for readers.", None),
-            PublicRoom::new("the reptile room", "Dedicated to the cold-blooded — snakes, lizards, and the people who care for them.", None),
-            PublicRoom::new("film club", "A relaxed space for movie lovers to discuss what they're watching or rewatching.", None),
-            PublicRoom::new("hiking log", "Post trail photos, plan trips, or trade gear advice with fellow hikers and backpackers.", None),
-            PublicRoom::new("language exchange", "Practice with native speakers, ask questions, and connect across cultures.", None),
-            PublicRoom::new("photography zone", "A space for camera lovers to share tips, talk gear, and show their latest shots.", None),
-            PublicRoom::new("coding corner", "Whether you're just learning or shipping features, join in for code talk and project chat.", None),
-            PublicRoom::new("run tracker", "Log your runs, talk about shoes or routes, and stay motivated with others.", None),
-            PublicRoom::new("board game night", "Pull up a chair for talk about tabletop games — casual or competitive.", None),
-            PublicRoom::new("brazilian kitchen", "From feijoada to street food, share recipes and stories from Brazilian cooking.", None),
-            PublicRoom::new("the forge", "For metalworkers, tool collectors, and anyone who likes building things with their hands.", None),
-        ];
-
-        PublicRooms(rooms)
-    }
-}
\ No newline at end of file
+        PublicRoomsCache{listed: DateTime::UNIX_EPOCH, rooms: Vec::new(), attachments: BTreeSet::new(), chunks: BTreeMap::new(), directory: Vec::new()}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pelican_ui::air::OrangeSecret;
+
+    fn decode(record: serde_json::Value) -> Message {
+        let mut message: Message = serde_json::from_value(record).unwrap();
+        message.upgrade();
+        message
+    }
+
+    #[test]
+    fn baseline_message_gets_derived_id() {
+        let author = OrangeSecret::new().name();
+        let timestamp: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        let message = decode(serde_json::json!(["hi", timestamp, author, false]));
+        let hash = Id::hash(&(author.clone(), timestamp));
+        assert_eq!(message.id(), Uuid::from_slice(&hash[..16]).unwrap());
+        assert_eq!(message.message(), "hi");
+        assert_eq!(message.kind(), &MessageKind::Text);
+        assert_eq!(decode(serde_json::json!(["hi", timestamp, author, false, []])).id(), message.id());
+    }
+
+    #[test]
+    fn baseline_join_marker_becomes_joined() {
+        let author = OrangeSecret::new().name();
+        let message = decode(serde_json::json!(["__system__joined", Utc::now(), author, true]));
+        assert_eq!(message.kind(), &MessageKind::Joined(None));
+        assert!(message.message().is_empty());
+        assert!(message.is_system());
+    }
+
+    #[test]
+    fn typed_join_marker_stays_text() {
+        let author = OrangeSecret::new().name();
+        let message = decode(serde_json::json!(["__system__joined", Utc::now(), author, false]));
+        assert_eq!(message.kind(), &MessageKind::Text);
+        assert_eq!(message.message(), "__system__joined");
+    }
+
+    #[test]
+    fn upgraded_message_round_trips() {
+        let author = OrangeSecret::new().name();
+        let message = decode(serde_json::json!(["hi", Utc::now(), author, false]));
+        let encoded = serde_json::to_value(&message).unwrap();
+        assert_eq!(decode(encoded), message);
+    }
+
+    fn file(author: &OrangeName, size: u64) -> Message {
+        Message::from(String::new(), author.clone()).with_attachment(Attachment::File("notes.txt".to_string(), size))
+    }
+
+    fn chunk(message: &Message, index: u32, count: u32, bytes: &[u8]) -> Blob {
+        Blob::Chunk(message.id(), message.author().clone(), index, count, STANDARD.encode(bytes))
+    }
+
+    #[test]
+    fn chunk_count_rounds_up() {
+        assert_eq!(Blob::count(0), 1);
+        assert_eq!(Blob::count(1), 1);
+        assert_eq!(Blob::count(Blob::CHUNK_SIZE as u64), 1);
+        assert_eq!(Blob::count(Blob::CHUNK_SIZE as u64 + 1), 2);
+    }
+
+    #[test]
+    fn read_cuts_file_into_chunks() {
+        let path = std::env::temp_dir().join(format!("messages-{}", Uuid::new_v4()));
+        let bytes = (0..Blob::CHUNK_SIZE + 10).map(|i| i as u8).collect::<Vec<_>>();
+        std::fs::write(&path, &bytes).unwrap();
+        let size = bytes.len() as u64;
+        assert_eq!(STANDARD.decode(Blob::read(&path, 0, size).unwrap()).unwrap(), bytes[..Blob::CHUNK_SIZE]);
+        assert_eq!(STANDARD.decode(Blob::read(&path, 1, size).unwrap()).unwrap(), bytes[Blob::CHUNK_SIZE..]);
+        assert!(Blob::read(&path, 0, size - 1).is_none());
+        std::fs::remove_file(&path).unwrap();
+        assert!(Blob::read(&path, 0, size).is_none());
+    }
+
+    #[test]
+    fn chunk_fits_only_its_file() {
+        let author = OrangeSecret::new().name();
+        let size = Blob::CHUNK_SIZE as u64 + 10;
+        let message = file(&author, size);
+        assert!(chunk(&message, 0, 2, &vec![0; Blob::CHUNK_SIZE]).fits(&message));
+        assert!(chunk(&message, 1, 2, &[0; 10]).fits(&message));
+        assert!(!chunk(&message, 1, 2, &[0; 9]).fits(&message));
+        assert!(!chunk(&message, 2, 2, &[]).fits(&message));
+        assert!(!chunk(&message, 0, 3, &vec![0; Blob::CHUNK_SIZE]).fits(&message));
+        let stranger = Blob::Chunk(message.id(), OrangeSecret::new().name(), 1, 2, STANDARD.encode([0; 10]));
+        assert!(!stranger.fits(&message));
+        assert!(!chunk(&file(&author, size), 1, 2, &[0; 10]).fits(&message));
+        assert!(!Blob::Image(message.id(), author, STANDARD.encode([0; 10])).fits(&message));
+    }
+
+    #[test]
+    fn image_fits_within_a_chunk() {
+        let author = OrangeSecret::new().name();
+        let message = Message::from(String::new(), author.clone()).with_attachment(Attachment::Image(String::new()));
+        assert!(Blob::Image(message.id(), author.clone(), STANDARD.encode(vec![0; Blob::CHUNK_SIZE])).fits(&message));
+        assert!(!Blob::Image(message.id(), author.clone(), STANDARD.encode(vec![0; Blob::CHUNK_SIZE + 1])).fits(&message));
+        assert!(!Blob::Image(message.id(), author, "not base64!".to_string()).fits(&message));
+    }
+
+    #[test]
+    fn first_image_wins() {
+        let author = OrangeSecret::new().name();
+        let key = Uuid::new_v4();
+        let (mut stored, mut chunks) = (BTreeSet::new(), BTreeMap::new());
+        let first = Attachments::arrive(&mut stored, &mut chunks, Blob::Image(key, author.clone(), "first".to_string()));
+        assert!(matches!(first, Some(Arrival::Whole(data)) if data == "first"));
+        assert!(Attachments::arrive(&mut stored, &mut chunks, Blob::Image(key, author, "second".to_string())).is_none());
+    }
+
+    #[test]
+    fn chunks_reassemble_once_all_arrive() {
+        let author = OrangeSecret::new().name();
+        let message = file(&author, 6);
+        let (mut stored, mut chunks) = (BTreeSet::new(), BTreeMap::new());
+        let mut pieces = BTreeMap::new();
+        for blob in [chunk(&message, 1, 3, b"cd"), chunk(&message, 1, 3, b"xx"), chunk(&message, 0, 3, b"ab"), chunk(&message, 2, 3, b"ef")] {
+            match Attachments::arrive(&mut stored, &mut chunks, blob) {
+                Some(Arrival::Piece(index, data, count)) => {
+                    assert!(pieces.insert(index, data).is_none());
+                    assert_eq!(count.is_some(), pieces.len() == 3);
+                },
+                Some(Arrival::Whole(_)) => panic!("a chunk is never whole"),
+                None => assert_eq!(pieces.len(), 1),
+            }
+        }
+        assert!(stored.contains(&message.id()) && chunks.is_empty());
+        let data = Attachments::assemble(pieces.into_values().collect());
+        assert_eq!(STANDARD.decode(data).unwrap(), b"abcdef");
+        assert!(Attachments::arrive(&mut stored, &mut chunks, chunk(&message, 0, 3, b"ab")).is_none());
+    }
+
+    /// A room made by an admin with two members, in that order.
+    fn group() -> (OrangeName, OrangeName, OrangeName, Vec<Member>) {
+        let (admin, member, other) = (OrangeSecret::new().name(), OrangeSecret::new().name(), OrangeSecret::new().name());
+        let mut members = Vec::new();
+        Membership::Join(Member::new(admin.clone(), None)).apply(&mut members);
+        Membership::Join(Member::new(member.clone(), Some(admin.clone()))).apply(&mut members);
+        Membership::Join(Member::new(other.clone(), Some(admin.clone()))).apply(&mut members);
+        (admin, member, other, members)
+    }
+
+    fn role(members: &[Member], name: &OrangeName) -> Option<Role> {
+        members.iter().find(|m| m.name() == name).map(Member::role)
+    }
+
+    #[test]
+    fn non_admin_membership_records_are_ignored() {
+        let (_, member, other, mut members) = group();
+        let stranger = OrangeSecret::new().name();
+        Membership::Role(other.clone(), Role::Admin, member.clone(), Utc::now()).apply(&mut members);
+        Membership::Leave(other.clone(), member.clone(), Utc::now()).apply(&mut members);
+        Membership::Join(Member::new(stranger.clone(), Some(member.clone()))).apply(&mut members);
+        assert_eq!(role(&members, &other), Some(Role::Member));
+        assert_eq!(role(&members, &stranger), None);
+        Membership::Leave(member.clone(), member.clone(), Utc::now()).apply(&mut members);
+        assert_eq!(role(&members, &member), None);
+    }
+
+    #[test]
+    fn admin_changes_roles_and_removes_members() {
+        let (admin, member, other, mut members) = group();
+        Membership::Role(member.clone(), Role::Admin, admin.clone(), Utc::now()).apply(&mut members);
+        Membership::Leave(other.clone(), admin.clone(), Utc::now()).apply(&mut members);
+        assert_eq!(role(&members, &member), Some(Role::Admin));
+        assert_eq!(role(&members, &other), None);
+        Membership::Role(admin.clone(), Role::Member, member, Utc::now()).apply(&mut members);
+        assert_eq!(role(&members, &admin), Some(Role::Member));
+    }
+
+    #[test]
+    fn last_admin_cannot_be_demoted() {
+        let (admin, member, _, mut members) = group();
+        Membership::Role(admin.clone(), Role::Member, admin.clone(), Utc::now()).apply(&mut members);
+        assert_eq!(role(&members, &admin), Some(Role::Admin));
+        assert_eq!(role(&members, &member), Some(Role::Member));
+    }
+
+    #[test]
+    fn longest_standing_member_takes_over_from_last_admin() {
+        let (admin, member, _, mut members) = group();
+        Membership::Leave(admin.clone(), admin, Utc::now()).apply(&mut members);
+        assert_eq!(role(&members, &member), Some(Role::Admin));
+    }
+
+    #[test]
+    fn non_admin_moderation_records_are_ignored() {
+        let (_, member, other, mut members) = group();
+        let mut messages = vec![Message::from("hi".to_string(), other.clone())];
+        let mut moderation = RoomModeration::default();
+        for record in [
+            Moderation::Mute(other.clone(), member.clone(), Utc::now()),
+            Moderation::Ban(other.clone(), member.clone(), Utc::now()),
+            Moderation::Purge(other.clone(), member.clone(), Utc::now()),
+            Moderation::Dismiss(messages[0].id(), member.clone(), Utc::now()),
+        ] {
+            record.apply(&mut moderation, &mut members, &mut messages);
+        }
+        assert_eq!(moderation, RoomModeration::default());
+        assert_eq!(role(&members, &other), Some(Role::Member));
+        assert!(!messages[0].is_deleted());
+    }
+
+    #[test]
+    fn admins_cannot_be_moderated() {
+        let (admin, member, _, mut members) = group();
+        Membership::Role(member.clone(), Role::Admin, admin.clone(), Utc::now()).apply(&mut members);
+        let mut messages = vec![Message::from("hi".to_string(), member.clone())];
+        let mut moderation = RoomModeration::default();
+        Moderation::Mute(member.clone(), admin.clone(), Utc::now()).apply(&mut moderation, &mut members, &mut messages);
+        Moderation::Ban(member.clone(), admin.clone(), Utc::now()).apply(&mut moderation, &mut members, &mut messages);
+        Moderation::Purge(member.clone(), admin, Utc::now()).apply(&mut moderation, &mut members, &mut messages);
+        assert_eq!(moderation, RoomModeration::default());
+        assert_eq!(role(&members, &member), Some(Role::Admin));
+        assert!(!messages[0].is_deleted());
+    }
+
+    #[test]
+    fn admin_ban_removes_member_and_their_messages() {
+        let (admin, _, other, mut members) = group();
+        let mut messages = vec![Message::from("hi".to_string(), other.clone())];
+        let mut moderation = RoomModeration::default();
+        Moderation::Mute(other.clone(), admin.clone(), Utc::now()).apply(&mut moderation, &mut members, &mut messages);
+        assert!(moderation.is_muted(&other));
+        Moderation::Ban(other.clone(), admin, Utc::now()).apply(&mut moderation, &mut members, &mut messages);
+        assert!(moderation.is_banned(&other) && !moderation.is_muted(&other));
+        assert_eq!(role(&members, &other), None);
+        assert!(messages[0].is_deleted());
+    }
+}